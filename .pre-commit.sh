@@ -36,3 +36,4 @@ cargo rustdoc --all-features -- -D warnings
 cargo test --workspace --all-features --all-targets
 cargo test --workspace --doc
 cargo clippy --all-features --all-targets -- -D warnings
+cargo clippy -p vy --no-default-features -- -D warnings
@@ -0,0 +1,169 @@
+//! Compile-time benchmark of the tuple and type-erased backends.
+//!
+//! Generates a crate with many large templates, once as plain element macros
+//! and once wrapped in `erased!`, and measures how long `cargo build` takes
+//! to rebuild each of them. Dependencies are built once up front and shared
+//! by both crates, so only the templates themselves are measured.
+//!
+//! Run it with `cargo bench --bench compile`. The number of templates can be
+//! changed with the `VY_BENCH_TEMPLATES` environment variable.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+const RUNS: usize = 3;
+
+fn template(i: usize, erased: bool) -> String {
+    let body = format!(
+        r##"html!(
+        lang = "en",
+        head!(
+            meta!(charset = "UTF-8"),
+            title!(title, " - page {i}"),
+            link!(rel = "stylesheet", href = "/style.css")
+        ),
+        body!(
+            header!(nav!(ul!(
+                li!(a!(href = "/", "Home")),
+                li!(a!(href = "/users", &user.name)),
+                li!(a!(href = "/about", "About"))
+            ))),
+            main!(
+                h1!(title),
+                section!(
+                    class = "profile",
+                    h2!(&user.name),
+                    p!("Email: ", a!(href = &user.email, &user.email)),
+                    p!("Score: ", user.score, " / ", user.max),
+                    p!("Active: ", user.active)
+                ),
+                table!(
+                    thead!(tr!(th!("ID"), th!("Name"), th!("Score"))),
+                    tbody!(rows.iter().map(|row| tr!(
+                        class? = row.active.then_some("active"),
+                        td!(row.id),
+                        td!(a!(href = format!("/users/{{}}", row.id), &row.name)),
+                        td!(row.score)
+                    )))
+                ),
+                form!(
+                    action = "/search",
+                    input!(r#type = "search", name = "q", value = query),
+                    button!(r#type = "submit", "Search")
+                )
+            ),
+            footer!(p!("Page ", {i}, " of ", user.max))
+        )
+    )"##
+    );
+    let body = if erased {
+        format!("erased!({body})")
+    } else {
+        body
+    };
+    format!(
+        "pub fn page_{i}<'a>(title: &'a str, query: &'a str, user: &'a User, \
+         rows: &'a [User]) -> impl IntoHtml + 'a {{\n    {body}\n}}\n\n"
+    )
+}
+
+fn generate(dir: &Path, vy: &Path, templates: usize, erased: bool) {
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"vy-bench-{}\"\nversion = \"0.0.0\"\nedition \
+             = \"2021\"\npublish = false\n\n[dependencies]\nvy = {{ path = \
+             {:?} }}\n\n[workspace]\n",
+            if erased { "erased" } else { "tuple" },
+            vy,
+        ),
+    )
+    .unwrap();
+    fs::copy(vy.join("Cargo.lock"), dir.join("Cargo.lock")).ok();
+
+    let mut src =
+        String::from(
+            "#![allow(clippy::all)]\nuse vy::prelude::*;\n\npub struct User \
+             {\n    pub id: u64,\n    pub name: String,\n    pub email: \
+             String,\n    pub score: f64,\n    pub max: u32,\n    pub active: \
+             bool,\n}\n\n",
+        );
+    for i in 0..templates {
+        src += &template(i, erased);
+    }
+    // Renders every template, so that they are instantiated.
+    src += "pub fn render(user: &User, rows: &[User]) -> Vec<String> {\n    \
+            vec![\n";
+    for i in 0..templates {
+        src += &format!(
+            "        page_{i}(\"Title\", \"q\", user, rows).into_string(),\n"
+        );
+    }
+    src += "    ]\n}\n";
+    fs::write(dir.join("src/lib.rs"), src).unwrap();
+}
+
+fn build(dir: &Path, target: &Path) -> Duration {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let start = Instant::now();
+    let status = Command::new(cargo)
+        .arg("build")
+        .arg("--quiet")
+        .current_dir(dir)
+        .env("CARGO_TARGET_DIR", target)
+        // Incremental builds would reuse most of the previous run.
+        .env("CARGO_INCREMENTAL", "0")
+        .status()
+        .expect("failed to run cargo");
+    let elapsed = start.elapsed();
+    assert!(status.success(), "failed to build {}", dir.display());
+    elapsed
+}
+
+/// Returns the fastest of several rebuilds of the crate in `dir`.
+fn measure(dir: &Path, target: &Path) -> Duration {
+    // Builds the dependencies.
+    build(dir, target);
+    let lib = dir.join("src/lib.rs");
+    let src = fs::read_to_string(&lib).unwrap();
+    (0..RUNS)
+        .map(|run| {
+            // Changes the source so that Cargo rebuilds it.
+            fs::write(&lib, format!("{src}// Run {run}\n")).unwrap();
+            build(dir, target)
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    // `cargo test` runs benchmarks without `--bench`, which would be too slow
+    // for this one.
+    if !env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+
+    let templates = env::var("VY_BENCH_TEMPLATES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(100);
+    let vy = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compile");
+    let target = root.join("target");
+
+    for erased in [false, true] {
+        let name = if erased { "erased" } else { "tuple" };
+        let dir = root.join(name);
+        generate(&dir, &vy, templates, erased);
+        let time = measure(&dir, &target);
+        println!(
+            "compile/{name}/{templates} templates: {:.2} s",
+            time.as_secs_f64()
+        );
+    }
+}
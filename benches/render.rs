@@ -0,0 +1,194 @@
+//! Rendering benchmarks.
+//!
+//! The page is rendered with both the default tuple backend and the
+//! type-erased [`Markup`] backend of `erased!`. See the `compile` benchmark
+//! for their compile times.
+//!
+//! [`Markup`]: vy::Markup
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use vy::prelude::*;
+
+struct Row {
+    id: u64,
+    name: String,
+    email: String,
+    score: f64,
+    active: bool,
+}
+
+fn rows(n: u64) -> Vec<Row> {
+    (0..n)
+        .map(|id| Row {
+            id,
+            name: format!("User <{id}>"),
+            email: format!("user{id}@example.com"),
+            score: id as f64 * 1.5,
+            active: id % 3 == 0,
+        })
+        .collect()
+}
+
+fn layout<'a>(
+    title: &'a str,
+    content: impl IntoHtml + 'a,
+) -> impl IntoHtml + 'a {
+    (
+        DOCTYPE,
+        html!(
+            lang = "en",
+            head!(
+                meta!(charset = "UTF-8"),
+                meta!(
+                    name = "viewport",
+                    content = "width=device-width,initial-scale=1"
+                ),
+                title!(title),
+                link!(rel = "stylesheet", href = "/static/style.css")
+            ),
+            body!(
+                header!(
+                    class = "site-header",
+                    nav!(ul!(
+                        li!(a!(href = "/", "Home")),
+                        li!(a!(href = "/users", "Users")),
+                        li!(a!(href = "/about", "About"))
+                    ))
+                ),
+                main!(class = "content", content),
+                footer!(p!("Copyright ", 2025, " Example Inc."))
+            )
+        ),
+    )
+}
+
+fn row(row: &Row) -> impl IntoHtml + '_ {
+    tr!(
+        class? = row.active.then_some("active"),
+        td!(row.id),
+        td!(a!(href = format!("/users/{}", row.id), &row.name)),
+        td!(&row.email),
+        td!(row.score),
+        td!(row.active)
+    )
+}
+
+fn table(rows: &[Row]) -> impl IntoHtml + '_ {
+    table!(
+        class = "users",
+        thead!(tr!(
+            th!("ID"),
+            th!("Name"),
+            th!("Email"),
+            th!("Score"),
+            th!("Active")
+        )),
+        tbody!(rows.iter().map(row))
+    )
+}
+
+fn page(rows: &[Row]) -> String {
+    layout("Users", (h1!("Users"), table(rows))).into_string()
+}
+
+fn erased_layout<'a>(
+    title: &'a str,
+    content: impl IntoHtml + 'a,
+) -> impl IntoHtml + 'a {
+    (
+        DOCTYPE,
+        erased!(html!(
+            lang = "en",
+            head!(
+                meta!(charset = "UTF-8"),
+                meta!(
+                    name = "viewport",
+                    content = "width=device-width,initial-scale=1"
+                ),
+                title!(title),
+                link!(rel = "stylesheet", href = "/static/style.css")
+            ),
+            body!(
+                header!(
+                    class = "site-header",
+                    nav!(ul!(
+                        li!(a!(href = "/", "Home")),
+                        li!(a!(href = "/users", "Users")),
+                        li!(a!(href = "/about", "About"))
+                    ))
+                ),
+                main!(class = "content", content),
+                footer!(p!("Copyright ", 2025, " Example Inc."))
+            )
+        )),
+    )
+}
+
+fn erased_row(row: &Row) -> impl IntoHtml + '_ {
+    erased!(tr!(
+        class? = row.active.then_some("active"),
+        td!(row.id),
+        td!(a!(href = format!("/users/{}", row.id), &row.name)),
+        td!(&row.email),
+        td!(row.score),
+        td!(row.active)
+    ))
+}
+
+fn erased_table(rows: &[Row]) -> impl IntoHtml + '_ {
+    erased!(table!(
+        class = "users",
+        thead!(tr!(
+            th!("ID"),
+            th!("Name"),
+            th!("Email"),
+            th!("Score"),
+            th!("Active")
+        )),
+        tbody!(rows.iter().map(erased_row))
+    ))
+}
+
+fn erased_page(rows: &[Row]) -> String {
+    erased_layout("Users", (h1!("Users"), erased_table(rows))).into_string()
+}
+
+fn bench_render(c: &mut Criterion) {
+    let small = rows(10);
+    let large = rows(1000);
+    assert_eq!(page(&small), erased_page(&small));
+
+    c.bench_function("page/10 rows", |b| b.iter(|| page(black_box(&small))));
+    c.bench_function("page/1000 rows", |b| b.iter(|| page(black_box(&large))));
+    c.bench_function("erased/10 rows", |b| {
+        b.iter(|| erased_page(black_box(&small)))
+    });
+    c.bench_function("erased/1000 rows", |b| {
+        b.iter(|| erased_page(black_box(&large)))
+    });
+}
+
+fn bench_escape(c: &mut Criterion) {
+    let inputs = [
+        (
+            "escape/text",
+            "Lorem ipsum <dolor> sit & amet, \"consectetur\" ".repeat(64),
+        ),
+        (
+            "escape/plain",
+            "Lorem ipsum dolor sit amet, consectetur adipiscing. ".repeat(64),
+        ),
+        ("escape/dense", "<&>\"".repeat(256)),
+        ("escape/unicode", "Smörgåsbord – ångström <è> ".repeat(64)),
+        ("escape/short", "Jane <Doe>".to_owned()),
+    ];
+
+    for (name, text) in &inputs {
+        c.bench_function(name, |b| {
+            b.iter(|| black_box(text.as_str()).into_string())
+        });
+    }
+}
+
+criterion_group!(benches, bench_render, bench_escape);
+criterion_main!(benches);
@@ -23,61 +23,205 @@
 // TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
 // SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
 use alloc::{
     alloc::{alloc, dealloc, handle_alloc_error, realloc},
     boxed::Box,
+    rc::Rc,
     string::String,
+    vec::Vec,
 };
+#[cfg(feature = "alloc")]
 use core::{
     alloc::Layout,
-    cmp, fmt,
+    any::Any,
     mem::ManuallyDrop,
     ops::{Add, AddAssign},
-    ptr, slice, str,
 };
+use core::{cmp, fmt, mem, ptr, slice, str};
+
+#[cfg(feature = "alloc")]
+use crate::{
+    context::Context,
+    etag::HashWriter,
+    pretty::{Mark, PrettyWriter},
+    text::TextWriter,
+};
+use crate::{escape::EscapePolicy, limit::LimitExceeded, Error};
 
 /// Buffer for rendered contents
 ///
 /// This struct is quite similar to `String`, but some methods are
 /// re-implemented for faster buffering.
+///
+/// Without the `alloc` feature, buffers are only created by
+/// [`IntoHtml::render_to_slice`](crate::IntoHtml::render_to_slice), and write
+/// into a fixed-size byte slice.
 pub struct Buffer {
     data: *mut u8,
     len: usize,
     capacity: usize,
+    error: Option<Error>,
+    #[cfg(feature = "alloc")]
+    context: Context,
+    policy: EscapePolicy,
+    max_len: usize,
+    max_depth: usize,
+    depth: usize,
+    /// Whether the memory is borrowed, in which case it is never reallocated
+    /// or freed.
+    borrowed: bool,
+    #[cfg(feature = "alloc")]
+    filter: Option<Filter>,
+    /// The start of a character split across byte-oriented writes.
+    #[cfg(feature = "alloc")]
+    partial: PartialChar,
+}
+
+/// Rewrites everything written into a buffer before it is stored.
+#[cfg(feature = "alloc")]
+pub(crate) enum Filter {
+    /// Keeps only the text content, see
+    /// [`TextOptions`](crate::text::TextOptions).
+    Text(Box<TextWriter>),
+    /// Indents the output, see [`pretty`](crate::pretty).
+    Pretty(Box<PrettyWriter>),
+    /// Hashes the output as it is written, see [`etag`](crate::etag).
+    Hash(Box<HashWriter>),
+    /// Discards the output around a [`fragment`](crate::fragment).
+    Discard,
+}
+
+#[cfg(feature = "alloc")]
+impl Filter {
+    /// Writes `html`, which is escaped markup.
+    fn write(&mut self, buf: &mut Buffer, html: &str) {
+        match self {
+            Self::Text(writer) => writer.markup(buf, html),
+            Self::Pretty(writer) => writer.write(buf, html, &[]),
+            Self::Hash(writer) => writer.write(buf, html),
+            Self::Discard => {}
+        }
+    }
+}
+
+/// The first bytes of a UTF-8 encoded character.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Default)]
+struct PartialChar {
+    bytes: [u8; 4],
+    len: usize,
 }
 
+#[cfg(feature = "alloc")]
+impl PartialChar {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    #[cfg(any(feature = "std", feature = "bytes"))]
+    #[inline]
+    fn set(&mut self, bytes: &[u8]) {
+        self.bytes[..bytes.len()].copy_from_slice(bytes);
+        self.len = bytes.len();
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl Buffer {
     /// Create an empty buffer
     #[inline]
-    pub const fn new() -> Buffer {
-        Self {
-            data: align_of::<u8>() as *mut u8, // dangling pointer
-            len: 0,
-            capacity: 0,
-        }
+    pub const fn new() -> Self {
+        // dangling pointer
+        unsafe { Self::from_raw_parts(align_of::<u8>() as *mut u8, 0, 0) }
     }
 
     /// Create a empty buffer with a particular capacity
     #[inline]
-    pub fn with_capacity(n: usize) -> Buffer {
+    pub fn with_capacity(n: usize) -> Self {
         if n == 0 {
             Self::new()
         } else {
-            Self {
-                data: safe_alloc(n),
+            unsafe { Self::from_raw_parts(safe_alloc(n), 0, n) }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `data` must be a block of `capacity` bytes allocated with the global
+    /// allocator, or dangling if `capacity` is zero, and its first `len`
+    /// bytes must be valid UTF-8.
+    #[inline]
+    const unsafe fn from_raw_parts(
+        data: *mut u8,
+        len: usize,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            data,
+            len,
+            capacity,
+            error: None,
+            context: Context::new(),
+            policy: EscapePolicy::Minimal,
+            max_len: usize::MAX,
+            max_depth: usize::MAX,
+            depth: 0,
+            borrowed: false,
+            filter: None,
+            partial: PartialChar {
+                bytes: [0; 4],
                 len: 0,
-                capacity: n,
-            }
+            },
+        }
+    }
+}
+
+impl Buffer {
+    /// Creates an empty buffer that writes into `capacity` bytes at `data`
+    /// and never allocates.
+    ///
+    /// Writes that do not fit are discarded, and record a
+    /// [`LimitExceeded::Length`] error instead.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid for writes of `capacity` bytes for as long as the
+    /// buffer is used, see [`SliceGuard`].
+    #[inline]
+    unsafe fn from_raw_slice(data: *mut u8, capacity: usize) -> Self {
+        Self {
+            data,
+            len: 0,
+            capacity,
+            error: None,
+            #[cfg(feature = "alloc")]
+            context: Context::new(),
+            policy: EscapePolicy::Minimal,
+            max_len: capacity,
+            max_depth: usize::MAX,
+            depth: 0,
+            borrowed: true,
+            #[cfg(feature = "alloc")]
+            filter: None,
+            #[cfg(feature = "alloc")]
+            partial: PartialChar::default(),
         }
     }
 
     /// Extracts a string slice containing the entire buffer
     #[inline]
     pub fn as_str(&self) -> &str {
-        unsafe {
-            let bytes = slice::from_raw_parts(self.data, self.len);
-            str::from_utf8_unchecked(bytes)
-        }
+        unsafe { str::from_utf8_unchecked(self.as_bytes()) }
+    }
+
+    /// Extracts a byte slice containing the entire buffer
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data, self.len) }
     }
 
     /// Returns an unsafe mutable pointer to the inner data
@@ -125,6 +269,10 @@ impl Buffer {
 
     /// Same as String::reserve
     ///
+    /// The capacity does not grow beyond the [maximum
+    /// length](Buffer::set_max_len), or at all for a buffer writing into a
+    /// byte slice, so fewer than `size` bytes may be available afterwards.
+    ///
     /// # Panics
     ///
     /// This method panics if `size` overflows `isize::MAX`.
@@ -133,30 +281,508 @@ impl Buffer {
         if size <= self.capacity - self.len {
             return;
         }
-        self.reserve_internal(size);
+        let size = cmp::min(size, self.max_len.saturating_sub(self.len));
+        if size > self.capacity - self.len {
+            _ = self.grow(size);
+        }
     }
 
     /// Same as String::reserve except that undefined behaviour can result if
     /// `size` overflows `isize::MAX`.
+    ///
+    /// Returns `false` without reserving if the buffer would exceed its
+    /// maximum length, in which case an error is recorded.
     #[inline]
-    pub(crate) unsafe fn reserve_small(&mut self, size: usize) {
+    #[must_use]
+    pub(crate) unsafe fn reserve_small(&mut self, size: usize) -> bool {
         debug_assert!(size <= isize::MAX as usize);
-        if self.len + size <= self.capacity {
-            return;
+        if self.len + size <= self.capacity || self.grow(size) {
+            return true;
+        }
+        self.set_error(LimitExceeded::Length(self.max_len));
+        false
+    }
+
+    /// Grows the buffer to fit `size` more bytes, returning `false` if it
+    /// would exceed the maximum length.
+    #[cfg(feature = "alloc")]
+    #[cold]
+    pub(crate) fn grow(&mut self, size: usize) -> bool {
+        if self.borrowed
+            || size > self.max_len - cmp::min(self.len, self.max_len)
+        {
+            return false;
         }
         self.reserve_internal(size);
+        true
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[inline]
+    pub(crate) fn grow(&mut self, _size: usize) -> bool {
+        false
     }
 
     #[inline]
     #[doc(hidden)]
     pub fn clear(&mut self) {
+        self.rewind(0);
         self.len = 0;
+        #[cfg(feature = "alloc")]
+        {
+            self.partial.len = 0;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Empties this buffer and resets everything but its allocation to the
+    /// state of a new buffer: the error, context, escape policy, limits and
+    /// filter.
+    #[cfg(feature = "std")]
+    pub(crate) fn reset(&mut self) {
+        self.len = 0;
+        self.error = None;
+        self.context = Context::new();
+        self.policy = EscapePolicy::Minimal;
+        self.max_len = usize::MAX;
+        self.max_depth = usize::MAX;
+        self.depth = 0;
+        self.filter = None;
+        self.partial.len = 0;
+    }
+
+    /// Replaces the filter applied to everything written into this buffer,
+    /// returning the previous one.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn set_filter(
+        &mut self,
+        filter: Option<Filter>,
+    ) -> Option<Filter> {
+        mem::replace(&mut self.filter, filter)
+    }
+
+    /// Returns whether writes go through a filter, in which case they must
+    /// use [`Buffer::push_str`] rather than write into the spare capacity.
+    #[inline]
+    pub(crate) fn is_filtered(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        return self.filter.is_some();
+        #[cfg(not(feature = "alloc"))]
+        false
+    }
+
+    /// Returns whether a filter rewrites the output, which is then no longer
+    /// the HTML written into this buffer.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn rewrites_output(&self) -> bool {
+        matches!(self.filter, Some(Filter::Text(_) | Filter::Pretty(_)))
+    }
+
+    /// Keeps the output from `start` on, which may still be rewritten, from
+    /// being hashed until [`Buffer::release`] is called with the returned
+    /// value.
+    #[inline]
+    pub(crate) fn hold(&mut self, start: usize) -> usize {
+        #[cfg(feature = "alloc")]
+        if let Some(Filter::Hash(writer)) = &mut self.filter {
+            return writer.hold(start);
+        }
+        let _ = start;
+        usize::MAX
+    }
+
+    #[inline]
+    pub(crate) fn release(&mut self, hold: usize) {
+        #[cfg(feature = "alloc")]
+        if let Some(Filter::Hash(writer)) = &mut self.filter {
+            writer.release(hold);
+        }
+        let _ = hold;
+    }
+
+    /// Notes that the output from `start` on is about to be rewritten.
+    #[inline]
+    fn rewind(&mut self, start: usize) {
+        #[cfg(feature = "alloc")]
+        if let Some(Filter::Hash(writer)) = &mut self.filter {
+            writer.rewind(start);
+        }
+        let _ = start;
+    }
+
+    #[cfg(feature = "alloc")]
+    #[cold]
+    fn push_filtered(&mut self, data: &str) {
+        if let Some(mut filter) = self.filter.take() {
+            filter.write(self, data);
+            self.filter = Some(filter);
+        }
     }
 
-    /// Converts a `Buffer` into a `String` without copy/realloc operation.
+    /// Writes static HTML with its structural marks.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn push_marked(&mut self, html: &str, marks: &[Mark]) {
+        match self.filter.take() {
+            Some(Filter::Pretty(mut writer)) => {
+                writer.write(self, html, marks);
+                self.filter = Some(Filter::Pretty(writer));
+            }
+            filter => {
+                self.filter = filter;
+                self.push_str(html);
+            }
+        }
+    }
+
+    /// Writes unescaped text, returning `false` if this buffer does not keep
+    /// only the text content, in which case the text must be escaped.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn push_filtered_text(&mut self, text: &str) -> bool {
+        match self.filter.take() {
+            Some(Filter::Text(mut writer)) => {
+                writer.text(self, text);
+                self.filter = Some(Filter::Text(writer));
+                true
+            }
+            filter => {
+                self.filter = filter;
+                false
+            }
+        }
+    }
+
+    /// Removes the bytes in `start..end`, shifting the rest of the buffer
+    /// back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` or `end` do not lie on a `char` boundary.
+    pub(crate) fn remove(&mut self, start: usize, end: usize) {
+        assert!(start <= end && end <= self.len);
+        assert!(self.as_str().is_char_boundary(start));
+        assert!(self.as_str().is_char_boundary(end));
+        self.rewind(start);
+        // SAFETY: both ranges are within the initialized part of the buffer.
+        unsafe {
+            ptr::copy(self.data.add(end), self.data.add(start), self.len - end);
+        }
+        self.len -= end - start;
+    }
+
+    /// Replaces the characters in `self[start..]` for which `replace` writes
+    /// a replacement into its scratch space and returns its length, without
+    /// allocating a copy.
+    ///
+    /// Replacements may be shorter or longer than the character they
+    /// replace, but must not contain characters that are replaced. If the
+    /// result does not fit, `self[start..]` is removed and an error is
+    /// recorded.
+    pub(crate) fn replace_chars(
+        &mut self,
+        start: usize,
+        replace: impl Fn(char, &mut [u8; 16]) -> Option<usize>,
+    ) {
+        let mut scratch = [0; 16];
+        let mut shrinks = false;
+        let mut extra = 0;
+        for ch in self.as_str()[start..].chars() {
+            match replace(ch, &mut scratch) {
+                Some(n) if n > ch.len_utf8() => extra += n - ch.len_utf8(),
+                Some(_) => shrinks = true,
+                None => {}
+            }
+        }
+        if !shrinks && extra == 0 {
+            return;
+        }
+        self.rewind(start);
+
+        if shrinks {
+            self.shrink_chars(start, &replace);
+            extra = self.as_str()[start..]
+                .chars()
+                .filter_map(|ch| {
+                    replace(ch, &mut scratch)?.checked_sub(ch.len_utf8())
+                })
+                .sum();
+            if extra == 0 {
+                return;
+            }
+        }
+
+        let end = self.len;
+        // SAFETY: `extra` is at most 15 bytes per byte of a string slice.
+        if !unsafe { self.reserve_small(extra) } {
+            self.truncate(start);
+            return;
+        }
+
+        // Move the characters back to their final position, starting from
+        // the end, so that every character is read before it is overwritten.
+        //
+        // SAFETY: the capacity was reserved above, and the buffer only
+        // contains valid UTF-8, so every character starts at a byte that is
+        // not a continuation byte. Only the replacements counted in `extra`
+        // are written, so `write` never falls behind `read`.
+        unsafe {
+            let data = self.data;
+            let mut read = end;
+            let mut write = end + extra;
+            while read > start {
+                let mut begin = read - 1;
+                while (*data.add(begin) as i8) < -0x40 {
+                    begin -= 1;
+                }
+                let bytes =
+                    slice::from_raw_parts(data.add(begin), read - begin);
+                let ch =
+                    str::from_utf8_unchecked(bytes).chars().next().unwrap();
+                match replace(ch, &mut scratch) {
+                    Some(n) if n > read - begin => {
+                        write -= n;
+                        ptr::copy_nonoverlapping(
+                            scratch.as_ptr(),
+                            data.add(write),
+                            n,
+                        );
+                    }
+                    _ => {
+                        write -= read - begin;
+                        ptr::copy(
+                            data.add(begin),
+                            data.add(write),
+                            read - begin,
+                        );
+                    }
+                }
+                read = begin;
+            }
+            debug_assert_eq!(read, write);
+        }
+        self.len = end + extra;
+    }
+
+    /// Writes the replacements of `replace_chars` that are not longer than
+    /// the character they replace, moving the rest of `self[start..]` to the
+    /// front.
+    fn shrink_chars(
+        &mut self,
+        start: usize,
+        replace: &impl Fn(char, &mut [u8; 16]) -> Option<usize>,
+    ) {
+        let mut scratch = [0; 16];
+        // SAFETY: `write` never passes `read`, since only replacements that
+        // are not longer than their character are written, so the bytes from
+        // `read` on are still the original UTF-8.
+        unsafe {
+            let data = self.data;
+            let mut read = start;
+            let mut write = start;
+            while read < self.len {
+                let rest =
+                    slice::from_raw_parts(data.add(read), self.len - read);
+                let ch = str::from_utf8_unchecked(rest).chars().next().unwrap();
+                let width = ch.len_utf8();
+                match replace(ch, &mut scratch) {
+                    Some(n) if n <= width => {
+                        ptr::copy_nonoverlapping(
+                            scratch.as_ptr(),
+                            data.add(write),
+                            n,
+                        );
+                        write += n;
+                    }
+                    _ => {
+                        ptr::copy(data.add(read), data.add(write), width);
+                        write += width;
+                    }
+                }
+                read += width;
+            }
+            self.len = write;
+        }
+    }
+
+    /// Shortens this buffer to the specified length
+    ///
+    /// If `new_len` is greater than the buffer's current length, this has no
+    /// effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` does not lie on a `char` boundary.
+    #[inline]
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len {
+            assert!(self.as_str().is_char_boundary(new_len));
+            self.rewind(new_len);
+            self.len = new_len;
+        }
+    }
+
+    /// Records an error that aborts rendering.
+    ///
+    /// Only the first error is kept, later errors are discarded.
+    #[inline]
+    pub fn set_error(&mut self, error: impl Into<Error>) {
+        if self.error.is_none() {
+            self.error = Some(error.into());
+        }
+    }
+
+    /// Returns the error that aborted rendering, if any.
+    #[inline]
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Adds a value to the render [`Context`], returning the previous value
+    /// of the same type, if any.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn provide<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.context.provide(value)
+    }
+
+    /// Returns a reference to the value of type `T` in the render
+    /// [`Context`], if any.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn use_context<T: Any>(&self) -> Option<&T> {
+        self.context.get()
+    }
+
+    /// Returns the render [`Context`].
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns a mutable reference to the render [`Context`].
+    #[cfg(feature = "alloc")]
     #[inline]
-    pub fn into_string(self) -> String {
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    /// Replaces the render [`Context`], returning the previous one.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn set_context(&mut self, context: Context) -> Context {
+        mem::replace(&mut self.context, context)
+    }
+
+    /// Returns the [`EscapePolicy`] used to escape text written into this
+    /// buffer.
+    #[inline]
+    pub fn escape_policy(&self) -> EscapePolicy {
+        self.policy
+    }
+
+    /// Sets the [`EscapePolicy`] used to escape text written into this
+    /// buffer, returning the previous one.
+    #[inline]
+    pub fn set_escape_policy(&mut self, policy: EscapePolicy) -> EscapePolicy {
+        mem::replace(&mut self.policy, policy)
+    }
+
+    /// Returns the maximum length of the output in bytes, if any.
+    #[inline]
+    pub fn max_len(&self) -> Option<usize> {
+        (self.max_len != usize::MAX).then_some(self.max_len)
+    }
+
+    /// Sets the maximum length of the output in bytes.
+    ///
+    /// Writes that would make the buffer longer are discarded, and record a
+    /// [`LimitExceeded::Length`] error instead. The buffer never grows beyond
+    /// this length, but output may still fill any capacity allocated before
+    /// the limit was set.
+    ///
+    /// The length of a buffer writing into a byte slice is always limited to
+    /// the length of its slice.
+    #[inline]
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len.unwrap_or(usize::MAX);
+        if self.borrowed {
+            self.max_len = cmp::min(self.max_len, self.capacity);
+        }
+    }
+
+    /// Returns the maximum nesting depth, if any.
+    #[inline]
+    pub fn max_depth(&self) -> Option<usize> {
+        (self.max_depth != usize::MAX).then_some(self.max_depth)
+    }
+
+    /// Sets the maximum nesting depth of [`nested`](crate::limit::nested)
+    /// values.
+    ///
+    /// Values nested deeper are not rendered, and record a
+    /// [`LimitExceeded::Depth`] error instead.
+    #[inline]
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth.unwrap_or(usize::MAX);
+    }
+
+    /// Increases the nesting depth, returning `false` and recording an error
+    /// if it exceeds the maximum depth.
+    #[inline]
+    pub(crate) fn enter(&mut self) -> bool {
+        if self.depth >= self.max_depth {
+            self.set_error(LimitExceeded::Depth(self.max_depth));
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    #[inline]
+    pub(crate) fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Converts a `Buffer` into a `String`, or returns the error that aborted
+    /// rendering, discarding any partial output.
+    ///
+    /// Bytes written with [`std::io::Write`] or [`bytes::BufMut`] that end in
+    /// the middle of a character are an error.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn try_into_string(mut self) -> Result<String, Error> {
+        if self.partial.len > 0 {
+            let partial = self.partial;
+            self.set_error(str::from_utf8(partial.as_bytes()).unwrap_err());
+        }
+        match self.take_error() {
+            Some(error) => Err(error),
+            None => Ok(self.into_string()),
+        }
+    }
+
+    /// Converts a `Buffer` into a `String` without copy/realloc operation,
+    /// unless it writes into a byte slice.
+    ///
+    /// Any error recorded with [`Buffer::set_error`] is ignored, use
+    /// [`Buffer::try_into_string`] to handle it.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn into_string(mut self) -> String {
         debug_assert!(self.len <= self.capacity);
+        if self.borrowed {
+            return String::from(self.as_str());
+        }
+        // Drop the owned fields, which `ManuallyDrop` would leak.
+        self.error = None;
+        self.context = Context::new();
+        self.filter = None;
         let buf = ManuallyDrop::new(self);
 
         // SAFETY: This operations satisfy all requirements specified in
@@ -164,16 +790,38 @@ impl Buffer {
         unsafe { String::from_raw_parts(buf.data, buf.len, buf.capacity) }
     }
 
+    /// Converts a `Buffer` into a `Vec<u8>` without copy/realloc operation,
+    /// like [`Buffer::into_string`].
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.into_string().into_bytes()
+    }
+
+    /// Converts a `Buffer` into a `Box<str>`, which drops any excess capacity.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn into_boxed_str(self) -> Box<str> {
+        self.into_string().into_boxed_str()
+    }
+
     /// Appends a given string slice onto the end of this buffer
     #[inline]
     pub fn push_str(&mut self, data: &str) {
+        #[cfg(feature = "alloc")]
+        if self.filter.is_some() {
+            self.push_filtered(data);
+            return;
+        }
         let size = data.len();
 
         unsafe {
             // SAFETY: this operation won't overflow because slice cannot
             // exceeds isize::MAX bytes.
             // https://doc.rust-lang.org/reference/behavior-considered-undefined.html
-            self.reserve_small(size);
+            if !self.reserve_small(size) {
+                return;
+            }
 
             let p = self.data.add(self.len);
             ptr::copy_nonoverlapping(data.as_ptr(), p, size);
@@ -182,24 +830,80 @@ impl Buffer {
         debug_assert!(self.len <= self.capacity);
     }
 
+    /// Appends UTF-8 encoded bytes, which may start or end in the middle of a
+    /// character.
+    ///
+    /// The start of a character at the end of `bytes` is kept until the next
+    /// call completes it. If the bytes are not valid UTF-8, everything before
+    /// the invalid sequence is still appended.
+    #[cfg(any(feature = "std", feature = "bytes"))]
+    fn push_utf8(&mut self, mut bytes: &[u8]) -> Result<(), str::Utf8Error> {
+        if self.partial.len > 0 {
+            let mut char = self.partial;
+            let take = cmp::min(4 - char.len, bytes.len());
+            char.bytes[char.len..char.len + take]
+                .copy_from_slice(&bytes[..take]);
+            char.len += take;
+            self.partial.len = 0;
+            let valid = match str::from_utf8(char.as_bytes()) {
+                Ok(_) => char.len,
+                Err(err) if err.valid_up_to() > 0 => err.valid_up_to(),
+                Err(err) if err.error_len().is_none() => {
+                    self.partial = char;
+                    return Ok(());
+                }
+                Err(err) => return Err(err),
+            };
+            // SAFETY: the bytes were validated above.
+            self.push_str(unsafe {
+                str::from_utf8_unchecked(&char.as_bytes()[..valid])
+            });
+            bytes = &bytes[valid + take - char.len..];
+        }
+        match str::from_utf8(bytes) {
+            Ok(s) => self.push_str(s),
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                // SAFETY: the bytes were validated above.
+                self.push_str(unsafe { str::from_utf8_unchecked(valid) });
+                if err.error_len().is_some() {
+                    return Err(err);
+                }
+                self.partial.set(rest);
+            }
+        }
+        Ok(())
+    }
+
     /// Appends the given `char` to the end of this buffer
     #[inline]
     pub fn push(&mut self, data: char) {
         // Question: Is it safe to pass uninitialized memory to `encode_utf8`
         // function?
         unsafe {
-            self.reserve_small(4);
+            if self.is_filtered()
+                || self.len + 4 > self.capacity && !self.grow(4)
+            {
+                // There may still be room for fewer than 4 bytes.
+                self.push_str(data.encode_utf8(&mut [0; 4]));
+                return;
+            }
             let bp = self.data.add(self.len) as *mut [u8; 4];
             let result = data.encode_utf8(&mut *bp);
             self.len += result.len();
         }
     }
 
+    #[cfg(feature = "alloc")]
     #[inline]
     fn reserve_internal(&mut self, size: usize) {
         debug_assert!(size <= isize::MAX as usize);
+        debug_assert!(!self.borrowed);
 
-        let new_capacity = cmp::max(self.capacity * 2, self.capacity + size);
+        let new_capacity = cmp::min(
+            cmp::max(self.capacity * 2, self.capacity + size),
+            cmp::max(self.max_len, self.len + size),
+        );
         debug_assert!(new_capacity > self.capacity);
         self.data =
             unsafe { safe_realloc(self.data, self.capacity, new_capacity) };
@@ -210,6 +914,67 @@ impl Buffer {
     }
 }
 
+/// A buffer writing into a byte slice, for the duration of a render.
+///
+/// The buffer has no lifetime, so a render could swap it out of the `&mut
+/// Buffer` it is given and keep it. Since it would then write into memory
+/// that is no longer borrowed, the process is aborted if the buffer was
+/// replaced by the time the guard is dropped, even while unwinding.
+pub(crate) struct SliceGuard {
+    buf: Buffer,
+    data: *mut u8,
+}
+
+impl SliceGuard {
+    /// # Safety
+    ///
+    /// `data` must be valid for writes of `capacity` bytes until the guard is
+    /// dropped.
+    #[inline]
+    pub(crate) unsafe fn new(data: *mut u8, capacity: usize) -> Self {
+        Self {
+            buf: Buffer::from_raw_slice(data, capacity),
+            data,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn buf(&mut self) -> &mut Buffer {
+        &mut self.buf
+    }
+}
+
+impl Drop for SliceGuard {
+    fn drop(&mut self) {
+        if !self.buf.borrowed || !ptr::eq(self.buf.data, self.data) {
+            abort();
+        }
+    }
+}
+
+/// Aborts the process.
+#[cold]
+fn abort() -> ! {
+    #[cfg(feature = "std")]
+    std::process::abort();
+
+    // Panicking while panicking aborts.
+    #[cfg(not(feature = "std"))]
+    {
+        struct Abort;
+
+        impl Drop for Abort {
+            fn drop(&mut self) {
+                panic!("a buffer borrowing a byte slice escaped its render");
+            }
+        }
+
+        let _abort = Abort;
+        panic!("a buffer borrowing a byte slice escaped its render");
+    }
+}
+
+#[cfg(feature = "alloc")]
 #[inline(never)]
 fn safe_alloc(capacity: usize) -> *mut u8 {
     assert!(capacity > 0);
@@ -231,6 +996,7 @@ fn safe_alloc(capacity: usize) -> *mut u8 {
 ///
 /// - if `capacity > 0`, `capacity` is the same value that was used to allocate
 ///   the block of memory pointed by `ptr`.
+#[cfg(feature = "alloc")]
 #[cold]
 #[inline(never)]
 unsafe fn safe_realloc(
@@ -256,17 +1022,25 @@ unsafe fn safe_realloc(
     data
 }
 
+#[cfg(feature = "alloc")]
 impl Clone for Buffer {
     fn clone(&self) -> Self {
         unsafe {
             if self.is_empty() {
-                Self::new()
+                let mut buf = Self::new();
+                buf.policy = self.policy;
+                buf.max_len = self.max_len;
+                buf.max_depth = self.max_depth;
+                buf
             } else {
-                let buf = Self {
-                    data: safe_alloc(self.len),
-                    len: self.len,
-                    capacity: self.len,
-                };
+                let mut buf = Self::from_raw_parts(
+                    safe_alloc(self.len),
+                    self.len,
+                    self.len,
+                );
+                buf.policy = self.policy;
+                buf.max_len = self.max_len;
+                buf.max_depth = self.max_depth;
 
                 ptr::copy_nonoverlapping(self.data, buf.data, self.len);
                 buf
@@ -281,9 +1055,10 @@ impl fmt::Debug for Buffer {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl Drop for Buffer {
     fn drop(&mut self) {
-        if self.capacity != 0 {
+        if self.capacity != 0 && !self.borrowed {
             // SAFETY: when `self.capacity > 0`, `self.capacity` is the same
             // value used for allocate the block of memory pointed
             // by `self.data`.
@@ -304,25 +1079,201 @@ impl fmt::Write for Buffer {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl From<String> for Buffer {
     /// Shrink the data and pass raw pointer directory to buffer
     ///
     /// This operation is `O(1)`
     #[inline]
-    fn from(other: String) -> Buffer {
+    fn from(other: String) -> Self {
         let bs = other.into_boxed_str();
         let data = Box::leak(bs);
-        Buffer {
-            data: data.as_mut_ptr(),
-            len: data.len(),
-            capacity: data.len(),
+        unsafe {
+            Self::from_raw_parts(data.as_mut_ptr(), data.len(), data.len())
         }
     }
 }
 
+#[cfg(feature = "alloc")]
+impl From<Buffer> for String {
+    #[inline]
+    fn from(buf: Buffer) -> String {
+        buf.into_string()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Buffer> for Vec<u8> {
+    #[inline]
+    fn from(buf: Buffer) -> Vec<u8> {
+        buf.into_bytes()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Buffer> for Box<str> {
+    #[inline]
+    fn from(buf: Buffer) -> Box<str> {
+        buf.into_boxed_str()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Buffer> for Rc<str> {
+    /// Copies the contents into a new `Rc<str>`.
+    #[inline]
+    fn from(buf: Buffer) -> Rc<str> {
+        Rc::from(buf.as_str())
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl From<Buffer> for Arc<str> {
+    /// Copies the contents into a new `Arc<str>`.
+    #[inline]
+    fn from(buf: Buffer) -> Arc<str> {
+        Arc::from(buf.as_str())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<Buffer> for bytes::Bytes {
+    #[inline]
+    fn from(buf: Buffer) -> bytes::Bytes {
+        bytes::Bytes::from(buf.into_bytes())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<Buffer> for bytes::BytesMut {
+    #[inline]
+    fn from(buf: Buffer) -> bytes::BytesMut {
+        bytes::BytesMut::from(bytes::Bytes::from(buf))
+    }
+}
+
+/// Appends UTF-8 encoded bytes, like [`std::io::Write`].
+///
+/// Bytes that are not valid UTF-8 are discarded and record an error.
+///
+/// # Safety
+///
+/// [`BufMut::chunk_mut`](bytes::BufMut::chunk_mut) returns the spare
+/// capacity of the buffer, and
+/// [`BufMut::advance_mut`](bytes::BufMut::advance_mut) validates the bytes
+/// written into it before they become part of the buffer.
+#[cfg(feature = "bytes")]
+unsafe impl bytes::BufMut for Buffer {
+    #[inline]
+    fn remaining_mut(&self) -> usize {
+        cmp::min(self.max_len, isize::MAX as usize)
+            .saturating_sub(self.len + self.partial.len)
+    }
+
+    #[inline]
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        let partial = self.partial.len;
+        assert!(
+            cnt <= self.capacity - self.len - partial,
+            "cannot advance past the spare capacity"
+        );
+        let bytes =
+            slice::from_raw_parts(self.data.add(self.len), partial + cnt);
+        self.partial.len = 0;
+        let valid = match str::from_utf8(bytes) {
+            Ok(_) => bytes.len(),
+            Err(err) => {
+                if err.error_len().is_some() {
+                    self.set_error(err);
+                } else {
+                    self.partial.set(&bytes[err.valid_up_to()..]);
+                }
+                err.valid_up_to()
+            }
+        };
+        if self.is_filtered() {
+            let html = String::from(str::from_utf8_unchecked(&bytes[..valid]));
+            self.push_str(&html);
+        } else {
+            self.len += valid;
+        }
+    }
+
+    #[inline]
+    fn chunk_mut(&mut self) -> &mut bytes::buf::UninitSlice {
+        let partial = self.partial.len;
+        if self.capacity - self.len <= partial {
+            self.reserve(partial + 64);
+        }
+        let spare = self.capacity - self.len;
+        if spare <= partial {
+            return bytes::buf::UninitSlice::new(&mut []);
+        }
+        // SAFETY: the spare capacity is allocated and not initialized, and
+        // the start of a split character is written back into it, so that
+        // `advance_mut` finds it before the new bytes.
+        unsafe {
+            let data = self.data.add(self.len);
+            ptr::copy_nonoverlapping(
+                self.partial.bytes.as_ptr(),
+                data,
+                partial,
+            );
+            bytes::buf::UninitSlice::from_raw_parts_mut(
+                data.add(partial),
+                spare - partial,
+            )
+        }
+    }
+
+    #[inline]
+    fn put_slice(&mut self, src: &[u8]) {
+        if let Err(err) = self.push_utf8(src) {
+            self.set_error(err);
+        }
+    }
+}
+
+impl AsRef<str> for Buffer {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for Buffer {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// Appends UTF-8 encoded bytes, returning an [`InvalidData`] error for
+/// anything else.
+///
+/// A character may be split across writes, as when copying from a reader.
+///
+/// [`InvalidData`]: std::io::ErrorKind::InvalidData
+#[cfg(feature = "std")]
+impl std::io::Write for Buffer {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.push_utf8(bytes).map_err(|err| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+        })?;
+        Ok(bytes.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl From<&str> for Buffer {
     #[inline]
-    fn from(other: &str) -> Buffer {
+    fn from(other: &str) -> Self {
         let mut buf = Buffer::with_capacity(other.len());
 
         if !other.is_empty() {
@@ -343,16 +1294,18 @@ impl From<&str> for Buffer {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl Add<&str> for Buffer {
     type Output = Buffer;
 
     #[inline]
-    fn add(mut self, other: &str) -> Buffer {
+    fn add(mut self, other: &str) -> Self {
         self.push_str(other);
         self
     }
 }
 
+#[cfg(feature = "alloc")]
 impl AddAssign<&str> for Buffer {
     #[inline]
     fn add_assign(&mut self, other: &str) {
@@ -360,9 +1313,10 @@ impl AddAssign<&str> for Buffer {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl Default for Buffer {
     #[inline]
-    fn default() -> Buffer {
+    fn default() -> Self {
         Buffer::new()
     }
 }
@@ -402,6 +1356,158 @@ mod tests {
         assert_eq!(buffer.capacity(), 80);
     }
 
+    #[test]
+    fn truncate() {
+        let mut buf = Buffer::from("héllo");
+        buf.truncate(10);
+        assert_eq!(buf.as_str(), "héllo");
+        buf.truncate(3);
+        assert_eq!(buf.as_str(), "hé");
+    }
+
+    #[test]
+    #[should_panic]
+    fn truncate_char_boundary() {
+        Buffer::from("é").truncate(1);
+    }
+
+    #[test]
+    fn from_slice() {
+        let mut bytes = [0; 8];
+        let mut guard = unsafe { SliceGuard::new(bytes.as_mut_ptr(), 8) };
+        let buf = guard.buf();
+        buf.push_str("abc");
+        buf.push('é');
+        buf.reserve(100);
+        assert_eq!(buf.capacity(), 8);
+        buf.set_max_len(None);
+        assert_eq!(buf.max_len(), Some(8));
+        buf.push_str("defg");
+        assert_eq!(buf.as_str(), "abcé");
+        assert!(buf.error().is_some());
+        assert_eq!(buf.clone().into_string(), "abcé");
+        drop(guard);
+        assert_eq!(&bytes[..5], "abcé".as_bytes());
+    }
+
+    #[test]
+    fn replace_chars() {
+        let mut buf = Buffer::from("a<é>b");
+        buf.replace_chars(1, |ch, out| match ch {
+            'é' => {
+                out[..6].copy_from_slice(b"&#xE9;");
+                Some(6)
+            }
+            '>' => {
+                out[..4].copy_from_slice(b"&gt;");
+                Some(4)
+            }
+            _ => None,
+        });
+        assert_eq!(buf.as_str(), "a<&#xE9;&gt;b");
+
+        buf.remove(1, 8);
+        assert_eq!(buf.as_str(), "a&gt;b");
+
+        let mut buf = Buffer::from("<a\u{1}é\u{1}b>\u{1}");
+        buf.replace_chars(1, |ch, out| match ch {
+            '\u{1}' => Some(0),
+            'é' => {
+                out[0] = b'e';
+                Some(1)
+            }
+            '>' => {
+                out[..4].copy_from_slice(b"&gt;");
+                Some(4)
+            }
+            _ => None,
+        });
+        assert_eq!(buf.as_str(), "<aeb&gt;");
+
+        let mut bytes = [0; 4];
+        let mut guard = unsafe { SliceGuard::new(bytes.as_mut_ptr(), 4) };
+        let buf = guard.buf();
+        buf.push_str("a>");
+        buf.replace_chars(1, |_, out| {
+            out[..4].copy_from_slice(b"&gt;");
+            Some(4)
+        });
+        assert_eq!(buf.as_str(), "a");
+        assert!(buf.error().is_some());
+    }
+
+    #[test]
+    fn conversions() {
+        use std::io::Write;
+
+        let mut buf = Buffer::with_capacity(16);
+        let ch = 'é';
+        write!(buf, "<p>{ch}</p>").unwrap();
+        assert!(buf.write(&[0xff]).is_err());
+        assert_eq!(buf.as_bytes(), "<p>é</p>".as_bytes());
+
+        let mut split = Buffer::new();
+        for bytes in "a€b".as_bytes().chunks(2) {
+            split.write_all(bytes).unwrap();
+        }
+        assert_eq!(split.as_str(), "a€b");
+        split.write_all(&"€".as_bytes()[..2]).unwrap();
+        assert!(split.write(b"c").is_err());
+        split.write_all(&"€".as_bytes()[..1]).unwrap();
+        assert!(split.try_into_string().is_err());
+
+        let ptr = buf.as_mut_ptr();
+        let bytes = Vec::from(buf.clone());
+        assert_eq!(bytes, "<p>é</p>".as_bytes());
+        assert_eq!(&*Box::<str>::from(buf.clone()), "<p>é</p>");
+        assert_eq!(&*Rc::<str>::from(buf.clone()), "<p>é</p>");
+        assert_eq!(&*Arc::<str>::from(buf.clone()), "<p>é</p>");
+        let bytes = buf.into_bytes();
+        assert_eq!(bytes.as_ptr(), ptr);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn buf_mut() {
+        use bytes::BufMut;
+
+        let mut buf = Buffer::new();
+        buf.put_slice(b"<p>");
+        buf.put_slice(&"é</p>".as_bytes()[..1]);
+        assert_eq!(buf.as_str(), "<p>");
+        buf.put(&"é</p>".as_bytes()[1..]);
+        buf.put_u8(b'!');
+        assert_eq!(buf.as_str(), "<p>é</p>!");
+        assert!(buf.error().is_none());
+
+        let chunk = buf.chunk_mut();
+        chunk[..1].copy_from_slice(&"é".as_bytes()[..1]);
+        unsafe { buf.advance_mut(1) };
+        assert_eq!(buf.as_str(), "<p>é</p>!");
+        let chunk = buf.chunk_mut();
+        chunk[..1].copy_from_slice(&"é".as_bytes()[1..]);
+        unsafe { buf.advance_mut(1) };
+        assert_eq!(buf.as_str(), "<p>é</p>!é");
+
+        buf.put_u8(0xff);
+        assert_eq!(buf.as_str(), "<p>é</p>!é");
+        assert!(buf.error().is_some());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes_conversions() {
+        let mut buf = Buffer::new();
+        buf.push_str("<p>");
+        let ptr = buf.as_mut_ptr();
+
+        let bytes = bytes::Bytes::from(buf.clone());
+        assert_eq!(bytes, "<p>");
+        let bytes = bytes::BytesMut::from(buf);
+        assert_eq!(bytes, "<p>");
+        assert_eq!(bytes.as_ptr(), ptr);
+    }
+
     #[test]
     fn with_capacity() {
         let buffer = Buffer::with_capacity(1);
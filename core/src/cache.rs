@@ -0,0 +1,573 @@
+//! Caching rendered fragments.
+//!
+//! Parts of a page such as the navigation or the footer are often expensive
+//! to render but identical for most requests. A [`Cache`] renders such a
+//! fragment once per key and reuses its HTML afterwards:
+//!
+//! ```
+//! # use std::sync::LazyLock;
+//! # use vy::{cache::*, prelude::*};
+//! static NAV: LazyLock<Cache<&str>> =
+//!     LazyLock::new(|| Cache::new(LruStore::new(64)));
+//!
+//! fn nav(user: &'static str) -> impl IntoHtml {
+//!     // The key must cover every input of the fragment.
+//!     NAV.cached(user, move || nav!(a!(href = "/", "Home"), " ", user))
+//! }
+//!
+//! assert_eq!(
+//!     body!(nav("Ann")).into_string(),
+//!     "<body><nav><a href=\"/\">Home</a> Ann</nav></body>"
+//! );
+//! assert_eq!(
+//!     nav("Ann").into_string(),
+//!     "<nav><a href=\"/\">Home</a> Ann</nav>"
+//! );
+//! assert_eq!(NAV.stats(), CacheStats { hits: 1, misses: 1 });
+//! ```
+//!
+//! The HTML is kept in a [`CacheStore`]:
+//!
+//! - [`LocalStore`] is an unbounded map without synchronization, for caches
+//!   kept in a `thread_local!`.
+//! - [`LruStore`] keeps a fixed number of entries, evicting the least recently
+//!   used one.
+//! - [`TtlStore`] expires entries after a fixed duration.
+//!
+//! A fragment is cached as it was first rendered, including the escape
+//! policy and [context](crate::context) values of that buffer, so do not
+//! cache fragments that depend on them. Fragments that write a CSP
+//! [nonce](crate::csp) are rendered again for every response, since the
+//! nonce changes every time. Renders that fail or exceed a
+//! [limit](crate::limit) are not cached, and caches are bypassed while
+//! rendering a single [fragment](crate::fragment).
+
+use alloc::sync::Arc;
+use core::{
+    cell::RefCell,
+    hash::Hash,
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+use crate::{
+    csp::NonceWritten, escape::PreEscaped, fragment, Buffer, Error, IntoHtml,
+};
+
+/// Storage for the HTML of a [`Cache`].
+pub trait CacheStore<K> {
+    /// Returns the HTML stored for `key`, if any.
+    fn get(&self, key: &K) -> Option<Arc<str>>;
+
+    /// Stores the HTML for `key`, replacing any previous entry.
+    fn insert(&self, key: K, html: Arc<str>);
+
+    /// Removes all entries.
+    fn clear(&self);
+}
+
+/// Hit and miss counts of a [`Cache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of lookups that found a cached fragment.
+    pub hits: usize,
+    /// The number of lookups that had to render the fragment.
+    pub misses: usize,
+}
+
+/// A cache of rendered fragments, keyed by their inputs.
+pub struct Cache<K, S = LruStore<K>> {
+    store: S,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    key: PhantomData<fn(K)>,
+}
+
+impl<K, S: CacheStore<K>> Cache<K, S> {
+    /// Creates a cache that keeps its fragments in `store`.
+    #[inline]
+    pub const fn new(store: S) -> Self {
+        Self {
+            store,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            key: PhantomData,
+        }
+    }
+
+    /// Returns an [`IntoHtml`] value that writes the fragment cached for
+    /// `key`, or renders `html` and caches it.
+    ///
+    /// On a miss, the fragment is rendered straight into the buffer of the
+    /// surrounding template. Threads that miss the same key at the same time
+    /// may each render it.
+    #[inline]
+    pub fn cached<F, H>(&self, key: K, html: F) -> Cached<'_, K, S, F>
+    where
+        F: FnOnce() -> H,
+        H: IntoHtml,
+    {
+        Cached {
+            cache: self,
+            key,
+            html,
+        }
+    }
+
+    /// Returns the fragment cached for `key`, or renders `html` into a new
+    /// buffer and caches it.
+    ///
+    /// Errors encountered while rendering are returned, and not cached.
+    pub fn get_or_render<H: IntoHtml>(
+        &self,
+        key: K,
+        html: impl FnOnce() -> H,
+    ) -> Result<PreEscaped<Arc<str>>, Error> {
+        if let Some(html) = self.lookup(&key) {
+            return Ok(PreEscaped(html));
+        }
+        let html = Arc::<str>::from(html().try_into_string()?);
+        self.store.insert(key, html.clone());
+        Ok(PreEscaped(html))
+    }
+
+    /// Returns the hit and miss counts since the cache was created.
+    #[inline]
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the store of the cache.
+    #[inline]
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Removes all cached fragments.
+    #[inline]
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
+    fn lookup(&self, key: &K) -> Option<Arc<str>> {
+        let html = self.store.get(key);
+        let counter = match html {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        html
+    }
+}
+
+/// An [`IntoHtml`] value created by [`Cache::cached`].
+pub struct Cached<'a, K, S, F> {
+    cache: &'a Cache<K, S>,
+    key: K,
+    html: F,
+}
+
+impl<K, S, F, H> IntoHtml for Cached<'_, K, S, F>
+where
+    S: CacheStore<K>,
+    F: FnOnce() -> H,
+    H: IntoHtml,
+{
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        // The fragment being rendered may lie inside the cached HTML, and
+        // rewritten output is not HTML.
+        if fragment::is_partial(buf) || buf.rewrites_output() {
+            (self.html)().escape_and_write(buf);
+            return;
+        }
+        if let Some(html) = self.cache.lookup(&self.key) {
+            buf.push_str(&html);
+            return;
+        }
+        let start = buf.len();
+        let outer = buf.context_mut().remove::<NonceWritten>();
+        (self.html)().escape_and_write(buf);
+        let nonce = buf.context_mut().remove::<NonceWritten>().is_some();
+        if outer.is_some() || nonce {
+            buf.provide(NonceWritten);
+        }
+        // Output that was cut short, or that contains the nonce of this
+        // response, must not be served again.
+        if buf.error().is_none() && !nonce {
+            let html = Arc::from(&buf.as_str()[start..]);
+            self.cache.store.insert(self.key, html);
+        }
+    }
+}
+
+/// An unbounded store without synchronization.
+///
+/// Keep a cache using this store in a `thread_local!`:
+///
+/// ```
+/// # use vy::{cache::*, prelude::*};
+/// thread_local! {
+///     static FOOTER: Cache<u16, LocalStore<u16>> = Cache::new(LocalStore::new());
+/// }
+///
+/// let year = 2025;
+/// let footer = FOOTER
+///     .with(|cache| cache.get_or_render(year, || footer!("© ", year)))
+///     .unwrap();
+/// assert_eq!(footer.into_string(), "<footer>© 2025</footer>");
+/// ```
+pub struct LocalStore<K> {
+    entries: RefCell<HashMap<K, Arc<str>>>,
+}
+
+impl<K> LocalStore<K> {
+    /// Creates an empty store.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            entries: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the number of entries in the store.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    /// Returns `true` if the store has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K> Default for LocalStore<K> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash> CacheStore<K> for LocalStore<K> {
+    #[inline]
+    fn get(&self, key: &K) -> Option<Arc<str>> {
+        self.entries.borrow().get(key).cloned()
+    }
+
+    #[inline]
+    fn insert(&self, key: K, html: Arc<str>) {
+        self.entries.borrow_mut().insert(key, html);
+    }
+
+    #[inline]
+    fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+}
+
+/// A store that keeps up to a fixed number of entries, evicting the least
+/// recently used one when full.
+pub struct LruStore<K> {
+    lru: Mutex<Lru<K>>,
+    capacity: usize,
+}
+
+struct Lru<K> {
+    /// The entries with the tick of their last use.
+    entries: HashMap<K, (Arc<str>, u64)>,
+    /// The keys by the tick of their last use.
+    order: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K> Lru<K> {
+    #[inline]
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+impl<K> LruStore<K> {
+    /// Creates an empty store that keeps up to `capacity` entries.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lru: Mutex::new(Lru {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+            }),
+            capacity,
+        }
+    }
+
+    /// Returns the number of entries in the store.
+    #[inline]
+    pub fn len(&self) -> usize {
+        lock(&self.lru).entries.len()
+    }
+
+    /// Returns `true` if the store has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Eq + Hash + Clone> CacheStore<K> for LruStore<K> {
+    fn get(&self, key: &K) -> Option<Arc<str>> {
+        let mut lru = lock(&self.lru);
+        let tick = lru.next_tick();
+        let Lru { entries, order, .. } = &mut *lru;
+        let (html, used) = entries.get_mut(key)?;
+        let key = order.remove(used).expect("entry without order");
+        order.insert(tick, key);
+        *used = tick;
+        Some(html.clone())
+    }
+
+    fn insert(&self, key: K, html: Arc<str>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut lru = lock(&self.lru);
+        let tick = lru.next_tick();
+        if let Some((_, used)) = lru.entries.insert(key.clone(), (html, tick)) {
+            lru.order.remove(&used);
+        } else if lru.entries.len() > self.capacity {
+            if let Some((_, oldest)) = lru.order.pop_first() {
+                lru.entries.remove(&oldest);
+            }
+        }
+        lru.order.insert(tick, key);
+    }
+
+    fn clear(&self) {
+        let mut lru = lock(&self.lru);
+        lru.entries.clear();
+        lru.order.clear();
+    }
+}
+
+/// The entries of a [`TtlStore`] with their expiry, or `None` if they never
+/// expire.
+type TtlEntries<K> = HashMap<K, (Arc<str>, Option<Instant>)>;
+
+/// A store whose entries expire after a fixed duration.
+///
+/// When full, expired entries are removed first, then the entry closest to
+/// expiring. Entries whose expiry cannot be represented, such as with a
+/// duration of [`Duration::MAX`], never expire.
+pub struct TtlStore<K> {
+    entries: Mutex<TtlEntries<K>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl<K> TtlStore<K> {
+    /// Creates an empty store that keeps up to `capacity` entries for `ttl`
+    /// each.
+    #[inline]
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            capacity,
+        }
+    }
+
+    /// Returns the number of entries in the store, including expired ones
+    /// that were not removed yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    /// Returns `true` if the store has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Eq + Hash + Clone> CacheStore<K> for TtlStore<K> {
+    fn get(&self, key: &K) -> Option<Arc<str>> {
+        let mut entries = lock(&self.entries);
+        let (html, expires) = entries.get(key)?;
+        if is_live(*expires, Instant::now()) {
+            return Some(html.clone());
+        }
+        entries.remove(key);
+        None
+    }
+
+    fn insert(&self, key: K, html: Arc<str>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = lock(&self.entries);
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, (_, expires)| is_live(*expires, now));
+            if entries.len() >= self.capacity {
+                let soonest = entries
+                    .iter()
+                    .min_by_key(|(_, (_, expires))| {
+                        (expires.is_none(), *expires)
+                    })
+                    .map(|(key, _)| key.clone());
+                if let Some(soonest) = soonest {
+                    entries.remove(&soonest);
+                }
+            }
+        }
+        entries.insert(key, (html, now.checked_add(self.ttl)));
+    }
+
+    fn clear(&self) {
+        lock(&self.entries).clear();
+    }
+}
+
+#[inline]
+fn is_live(expires: Option<Instant>, now: Instant) -> bool {
+    expires.map_or(true, |expires| now < expires)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // The entries are valid even if a thread panicked while holding the lock.
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::{
+        csp::{Nonce, NonceAttr},
+        limit::Limits,
+    };
+
+    #[test]
+    fn hits_and_misses() {
+        let cache = Cache::new(LruStore::new(8));
+        let renders = &Cell::new(0);
+        let render = |key: u8| {
+            cache.cached(key, move || {
+                renders.set(renders.get() + 1);
+                ("<", key)
+            })
+        };
+
+        assert_eq!(("a", render(1)).into_string(), "a&lt;1");
+        assert_eq!(render(1).into_string(), "&lt;1");
+        assert_eq!(render(2).into_string(), "&lt;2");
+        assert_eq!(renders.get(), 2);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+
+        cache.clear();
+        assert_eq!(render(1).into_string(), "&lt;1");
+        assert_eq!(renders.get(), 3);
+    }
+
+    #[test]
+    fn failed_renders_are_not_cached() {
+        let cache = Cache::new(LocalStore::new());
+        let failed = || Err::<&str, _>(Error::msg("failed"));
+        assert!(cache.get_or_render((), failed).is_err());
+        assert!(cache.cached((), failed).try_into_string().is_err());
+        assert!(cache.store().is_empty());
+
+        let limits = Limits::new().max_len(4);
+        assert!(limits.try_render(cache.cached((), || "abcdef")).is_err());
+        assert!(cache.store().is_empty());
+
+        let html = cache.get_or_render((), || "abc").unwrap();
+        assert_eq!(&*html.0, "abc");
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 4 });
+    }
+
+    #[test]
+    fn lru() {
+        let store = LruStore::new(2);
+        store.insert('a', Arc::from("a"));
+        store.insert('b', Arc::from("b"));
+        assert!(store.get(&'a').is_some());
+        store.insert('c', Arc::from("c"));
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&'b').is_none());
+        assert_eq!(store.get(&'a').as_deref(), Some("a"));
+
+        store.insert('a', Arc::from("A"));
+        store.insert('d', Arc::from("d"));
+        assert_eq!(store.get(&'a').as_deref(), Some("A"));
+        assert!(store.get(&'c').is_none());
+
+        let store = LruStore::new(0);
+        store.insert('a', Arc::from("a"));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn ttl() {
+        let store = TtlStore::new(Duration::ZERO, 8);
+        store.insert('a', Arc::from("a"));
+        assert!(store.get(&'a').is_none());
+        assert!(store.is_empty());
+
+        let store = TtlStore::new(Duration::from_secs(3600), 2);
+        store.insert('a', Arc::from("a"));
+        store.insert('b', Arc::from("b"));
+        store.insert('c', Arc::from("c"));
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&'a').is_none());
+        assert_eq!(store.get(&'c').as_deref(), Some("c"));
+
+        let store = TtlStore::new(Duration::MAX, 1);
+        store.insert('a', Arc::from("a"));
+        assert_eq!(store.get(&'a').as_deref(), Some("a"));
+        store.insert('b', Arc::from("b"));
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(&'b').as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn nonces_are_not_cached() {
+        let cache = Cache::new(LocalStore::new());
+        let render = |nonce: &str| {
+            let mut buf = Buffer::new();
+            buf.provide(Nonce::new(nonce));
+            let outer = Cache::new(LocalStore::new());
+            let html = outer.cached((), || {
+                cache.cached((), || (PreEscaped("<script"), NonceAttr))
+            });
+            html.render_into(&mut buf);
+            assert!(outer.store().is_empty());
+            buf.into_string()
+        };
+
+        assert_eq!(render("a"), "<script nonce=\"a\"");
+        assert_eq!(render("b"), "<script nonce=\"b\"");
+        assert!(cache.store().is_empty());
+
+        assert_eq!(cache.cached((), || "<p>").into_string(), "&lt;p&gt;");
+        assert_eq!(cache.store().len(), 1);
+    }
+}
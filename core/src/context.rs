@@ -0,0 +1,256 @@
+//! Request-scoped values available while rendering.
+//!
+//! A [`Context`] is a map from types to values, carried by the [`Buffer`]
+//! so that deeply nested components can access values such as the current
+//! locale, user or CSP nonce without threading them through every function.
+//!
+//! ```
+//! # use vy::{context::*, prelude::*};
+//! struct Locale(&'static str);
+//!
+//! fn greeting() -> impl IntoHtml {
+//!     use_context(|locale: Option<&Locale>| match locale {
+//!         Some(Locale("sv")) => "Hej",
+//!         _ => "Hello",
+//!     })
+//! }
+//!
+//! let page = body!(h1!(greeting()));
+//!
+//! let mut buf = Buffer::new();
+//! buf.provide(Locale("sv"));
+//! page.render_into(&mut buf);
+//! assert_eq!(buf.into_string(), "<body><h1>Hej</h1></body>");
+//! ```
+//!
+//! Values must be `Send` and `Sync`, so that a [`Buffer`] carrying them can
+//! still be held across an `.await` in a multi-threaded server and returned
+//! to a shared buffer pool. Single-threaded request types
+//! built on `Rc` or `RefCell` can be provided as `Arc` and `Mutex` instead,
+//! or as the plain data that the templates read from them.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    any::{Any, TypeId},
+    fmt,
+    marker::PhantomData,
+};
+
+use crate::{Buffer, IntoHtml};
+
+type Entry = (TypeId, Box<dyn Any + Send + Sync>);
+
+/// A map of values keyed by their type.
+#[derive(Default)]
+pub struct Context {
+    entries: Vec<Entry>,
+}
+
+impl Context {
+    /// Creates an empty context.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a value to the context, returning the previous value of the same
+    /// type, if any.
+    ///
+    /// See the [module documentation](self) for why `T` must be `Send` and
+    /// `Sync`.
+    pub fn provide<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        let prev = self.remove::<T>();
+        self.entries.push((TypeId::of::<T>(), Box::new(value)));
+        prev
+    }
+
+    /// Adds a value to the context and returns it, for chaining.
+    #[inline]
+    pub fn with<T: Any + Send + Sync>(mut self, value: T) -> Self {
+        self.provide(value);
+        self
+    }
+
+    /// Returns a reference to the value of type `T`, if any.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        let id = TypeId::of::<T>();
+        self.entries
+            .iter()
+            .find(|(key, _)| *key == id)
+            .and_then(|(_, value)| value.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of type `T`, if any.
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        let id = TypeId::of::<T>();
+        self.entries
+            .iter_mut()
+            .find(|(key, _)| *key == id)
+            .and_then(|(_, value)| value.downcast_mut())
+    }
+
+    /// Removes the value of type `T` from the context and returns it.
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.take_entry(TypeId::of::<T>())
+            .and_then(|(_, value)| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Returns `true` if the context contains no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn take_entry(&mut self, id: TypeId) -> Option<Entry> {
+        let i = self.entries.iter().position(|(key, _)| *key == id)?;
+        Some(self.entries.swap_remove(i))
+    }
+
+    pub(crate) fn restore_entry(&mut self, entry: Option<Entry>) {
+        if let Some(entry) = entry {
+            self.entries.push(entry);
+        }
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
+
+/// Renders `html` with `value` provided in the context.
+///
+/// The previous value of the same type is restored afterwards, so the value
+/// is only visible to `html` and its descendants.
+#[inline]
+pub fn provide<T, H>(value: T, html: H) -> Provide<T, H>
+where
+    T: Any + Send + Sync,
+    H: IntoHtml,
+{
+    Provide { value, html }
+}
+
+/// An [`IntoHtml`] value created by [`provide`].
+pub struct Provide<T, H> {
+    value: T,
+    html: H,
+}
+
+impl<T, H> IntoHtml for Provide<T, H>
+where
+    T: Any + Send + Sync,
+    H: IntoHtml,
+{
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let prev = buf.context_mut().take_entry(TypeId::of::<T>());
+        buf.provide(self.value);
+        self.html.escape_and_write(buf);
+        buf.context_mut().remove::<T>();
+        buf.context_mut().restore_entry(prev);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.html.size_hint()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.html.exact_size()
+    }
+}
+
+/// Renders the value returned by `f`, which is given the value of type `T`
+/// from the context, if any.
+#[inline]
+pub fn use_context<T, F, H>(f: F) -> UseContext<T, F>
+where
+    T: Any,
+    F: FnOnce(Option<&T>) -> H,
+    H: IntoHtml,
+{
+    UseContext {
+        f,
+        _marker: PhantomData,
+    }
+}
+
+/// An [`IntoHtml`] value created by [`use_context`].
+pub struct UseContext<T, F> {
+    f: F,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T, F, H> IntoHtml for UseContext<T, F>
+where
+    T: Any,
+    F: FnOnce(Option<&T>) -> H,
+    H: IntoHtml,
+{
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let html = (self.f)(buf.use_context::<T>());
+        html.escape_and_write(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User(&'static str);
+
+    #[test]
+    fn provide_and_get() {
+        let mut cx = Context::new();
+        assert!(cx.is_empty());
+        assert_eq!(cx.provide(User("alice")), None);
+        assert_eq!(cx.get::<User>(), Some(&User("alice")));
+        assert_eq!(cx.provide(User("bob")), Some(User("alice")));
+        assert_eq!(cx.get::<u32>(), None);
+
+        cx.get_mut::<User>().unwrap().0 = "carol";
+        assert_eq!(cx.remove::<User>(), Some(User("carol")));
+        assert!(cx.is_empty());
+    }
+
+    #[test]
+    fn scoped_provide() {
+        fn name() -> impl IntoHtml {
+            use_context(|user: Option<&User>| user.map(|user| user.0))
+        }
+
+        let html = (
+            name(),
+            provide(User("bob"), (name(), provide(User("carol"), name()))),
+            name(),
+        );
+
+        let mut buf = Buffer::new();
+        buf.provide(User("alice"));
+        html.render_into(&mut buf);
+        assert_eq!(buf.use_context::<User>(), Some(&User("alice")));
+        assert_eq!(buf.into_string(), "alicebobcarolalice");
+
+        assert_eq!((name(), provide(String::new(), name())).into_string(), "");
+    }
+}
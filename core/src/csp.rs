@@ -0,0 +1,117 @@
+//! Support for a strict Content-Security-Policy.
+//!
+//! The `csp_nonce!` macro adds a `nonce` attribute to every `script`, `style`
+//! and stylesheet `link` element of the element it wraps, which is rendered
+//! when a [`Nonce`] is provided in the render
+//! [`Context`](crate::context::Context). Without a nonce, nothing is added.
+//!
+//! ```
+//! # use vy::{csp::Nonce, prelude::*};
+//! let page = || {
+//!     csp_nonce!(head!(script!(src = "/app.js"), style!("body{margin:0}")))
+//! };
+//!
+//! let mut buf = Buffer::new();
+//! buf.provide(Nonce::new("r4nd0m"));
+//! page().render_into(&mut buf);
+//! assert_eq!(
+//!     buf.into_string(),
+//!     "<head><script src=\"/app.js\" nonce=\"r4nd0m\"></script><style \
+//!      nonce=\"r4nd0m\">body{margin:0}</style></head>"
+//! );
+//!
+//! assert_eq!(
+//!     page().into_string(),
+//!     "<head><script \
+//!      src=\"/app.js\"></script><style>body{margin:0}</style></head>"
+//! );
+//! ```
+//!
+//! For hash-based policies, the `csp_hash!` macro computes the hash source
+//! of a static inline script or style at compile time.
+//!
+//! Nonces require the `alloc` feature.
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use core::any::TypeId;
+
+#[cfg(feature = "alloc")]
+use crate::escape::escape_into;
+use crate::{Buffer, IntoHtml};
+
+/// A per-response nonce for the `script-src` and `style-src` directives.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Nonce(String);
+
+#[cfg(feature = "alloc")]
+impl Nonce {
+    /// Creates a new nonce.
+    ///
+    /// The value should be generated from a cryptographically secure source
+    /// of randomness for every response.
+    #[inline]
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// Returns the nonce value.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Recorded in the render context when a nonce is written, so that caches
+/// do not store output containing the nonce of a single response.
+#[cfg(feature = "alloc")]
+pub(crate) struct NonceWritten;
+
+/// Writes a ` nonce="..."` attribute if a [`Nonce`] is provided in the render
+/// context.
+#[doc(hidden)]
+pub struct NonceAttr;
+
+impl IntoHtml for NonceAttr {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[cfg(feature = "alloc")]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let Some(entry) = buf.context_mut().take_entry(TypeId::of::<Nonce>())
+        else {
+            return;
+        };
+        if let Some(nonce) = entry.1.downcast_ref::<Nonce>() {
+            buf.push_str(" nonce=\"");
+            escape_into(buf, nonce.as_str());
+            buf.push('"');
+        }
+        buf.context_mut().restore_entry(Some(entry));
+        buf.provide(NonceWritten);
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    #[inline]
+    fn escape_and_write(self, _buf: &mut Buffer) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonce_attr() {
+        assert_eq!(NonceAttr.into_string(), "");
+
+        let mut buf = Buffer::new();
+        buf.provide(Nonce::new("a\"b"));
+        NonceAttr.render_into(&mut buf);
+        NonceAttr.render_into(&mut buf);
+        assert_eq!(buf.into_string(), " nonce=\"a&quot;b\" nonce=\"a&quot;b\"");
+    }
+}
@@ -31,6 +31,13 @@ macro_rules! impl_enum {
                         $( $name::$var(value) => value.size_hint(), )*
                     }
                 }
+
+                #[inline]
+                fn exact_size(&self) -> usize {
+                    match self {
+                        $( $name::$var(value) => value.exact_size(), )*
+                    }
+                }
             }
         )*
     };
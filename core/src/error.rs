@@ -0,0 +1,165 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::{error::Error as StdError, fmt};
+
+#[cfg(not(feature = "alloc"))]
+use crate::limit::LimitExceeded;
+
+/// An error that aborted rendering.
+///
+/// Any type implementing [`core::error::Error`] can be converted into this
+/// type, which makes it possible to embed a [`Result`] in a template and
+/// propagate its error through
+/// [`IntoHtml::try_into_string`](crate::IntoHtml::try_into_string).
+///
+/// Without the `alloc` feature, the only error is
+/// [`LimitExceeded`](crate::limit::LimitExceeded).
+pub struct Error {
+    #[cfg(feature = "alloc")]
+    inner: Box<dyn StdError + Send + Sync>,
+    #[cfg(not(feature = "alloc"))]
+    inner: LimitExceeded,
+}
+
+#[cfg(not(feature = "alloc"))]
+impl Error {
+    /// Returns a reference to the inner error if it is of type `E`.
+    #[inline]
+    pub fn downcast_ref<E: StdError + 'static>(&self) -> Option<&E> {
+        (&self.inner as &dyn StdError).downcast_ref()
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+impl From<LimitExceeded> for Error {
+    #[inline]
+    fn from(error: LimitExceeded) -> Self {
+        Self { inner: error }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Error {
+    /// Wraps an error.
+    #[inline]
+    pub fn new<E>(error: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        Self {
+            inner: Box::new(error),
+        }
+    }
+
+    /// Creates an error from a printable message.
+    #[inline]
+    pub fn msg<M>(message: M) -> Self
+    where
+        M: fmt::Display + fmt::Debug + Send + Sync + 'static,
+    {
+        Self::new(Message(message))
+    }
+
+    /// Returns a reference to the inner error if it is of type `E`.
+    #[inline]
+    pub fn downcast_ref<E: StdError + 'static>(&self) -> Option<&E> {
+        self.inner.downcast_ref()
+    }
+
+    /// Attempts to downcast the inner error to the concrete type `E`.
+    #[inline]
+    pub fn downcast<E: StdError + 'static>(self) -> Result<E, Self> {
+        match self.inner.downcast() {
+            Ok(error) => Ok(*error),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+
+    /// Consumes the error, returning the inner error.
+    #[inline]
+    pub fn into_inner(self) -> Box<dyn StdError + Send + Sync> {
+        self.inner
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<E> From<E> for Error
+where
+    E: StdError + Send + Sync + 'static,
+{
+    #[inline]
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Error> for Box<dyn StdError + Send + Sync> {
+    #[inline]
+    fn from(error: Error) -> Self {
+        error.inner
+    }
+}
+
+impl AsRef<dyn StdError + Send + Sync> for Error {
+    #[inline]
+    fn as_ref(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        #[cfg(feature = "alloc")]
+        return &*self.inner;
+        #[cfg(not(feature = "alloc"))]
+        return &self.inner;
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct Message<M>(M);
+
+#[cfg(feature = "alloc")]
+impl<M: fmt::Debug> fmt::Debug for Message<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M: fmt::Display> fmt::Display for Message<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M: fmt::Display + fmt::Debug> StdError for Message<M> {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn downcast() {
+        let error = Error::new(fmt::Error);
+        assert!(error.downcast_ref::<fmt::Error>().is_some());
+        assert!(error.downcast::<fmt::Error>().is_ok());
+    }
+
+    #[test]
+    fn message() {
+        let error = Error::msg("user not found");
+        assert_eq!(error.to_string(), "user not found");
+        assert!(error.downcast::<fmt::Error>().is_err());
+    }
+}
@@ -1,5 +1,10 @@
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
 use alloc::{borrow::Cow, string::String};
 
+#[cfg(feature = "alloc")]
+use crate::entities;
 use crate::{buffer::Buffer, IntoHtml};
 
 #[inline]
@@ -13,19 +18,245 @@ pub const fn escape_char(ch: char) -> Option<&'static str> {
     }
 }
 
+/// The set of characters escaped in text and attribute values.
+///
+/// The policy is selected per render with [`Buffer::set_escape_policy`], or
+/// for part of a document with [`with_policy`], and is honoured by every
+/// [`IntoHtml`] implementation that writes text. Static string literals in
+/// the element macros are trusted and escaped at compile time with the
+/// minimal policy.
+///
+/// ```
+/// # use vy::{escape::EscapePolicy, prelude::*};
+/// let text = String::from("it's a/b");
+///
+/// let mut buf = Buffer::new();
+/// buf.set_escape_policy(EscapePolicy::Strict);
+/// p!(class = "a/b", text).render_into(&mut buf);
+/// assert_eq!(buf.into_string(), "<p class=\"a/b\">it&#x27;s a&#x2F;b</p>");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum EscapePolicy {
+    /// Escapes `&`, `<`, `>` and `"`, which is sufficient for text and
+    /// double-quoted attribute values.
+    #[default]
+    Minimal,
+    /// Also escapes `'`, `/`, `` ` `` and `=`, as recommended by the OWASP
+    /// XSS prevention cheat sheet, which keeps single-quoted and unquoted
+    /// attribute values safe.
+    Strict,
+    /// Also escapes `'`, and removes the control characters and
+    /// non-characters that are not allowed in XML 1.0 documents.
+    Xml,
+}
+
+impl EscapePolicy {
+    /// Returns the replacement of `ch` under this policy, or `None` if it is
+    /// written as is.
+    #[inline]
+    pub const fn escape_char(self, ch: char) -> Option<&'static str> {
+        match (self, ch) {
+            (_, '&' | '<' | '>' | '"') => escape_char(ch),
+            (Self::Strict, '\'') => Some("&#x27;"),
+            (Self::Strict, '/') => Some("&#x2F;"),
+            (Self::Strict, '`') => Some("&#x60;"),
+            (Self::Strict, '=') => Some("&#x3D;"),
+            (Self::Xml, '\'') => Some("&#39;"),
+            (
+                Self::Xml,
+                '\0'..='\x08'
+                | '\x0B'
+                | '\x0C'
+                | '\x0E'..='\x1F'
+                | '\u{FFFE}'
+                | '\u{FFFF}',
+            ) => Some(""),
+            _ => None,
+        }
+    }
+}
+
+#[inline]
+const fn escape_byte(b: u8) -> Option<&'static str> {
+    escape_char(b as char)
+}
+
 /// Escapes all special HTML characters in `input` and writes the result into
-/// `buf`.
+/// `buf`, following the buffer's [`EscapePolicy`].
 #[inline]
 pub fn escape_into(output: &mut Buffer, input: &str) {
-    for ch in input.chars() {
-        match escape_char(ch) {
-            Some(esc) => output.push_str(esc),
-            _ => output.push(ch),
-        };
+    #[cfg(feature = "alloc")]
+    if output.is_filtered() && output.push_filtered_text(input) {
+        return;
+    }
+    match output.escape_policy() {
+        EscapePolicy::Minimal => escape_minimal(output, input),
+        policy => {
+            for ch in input.chars() {
+                match policy.escape_char(ch) {
+                    Some(esc) => output.push_str(esc),
+                    None => output.push(ch),
+                }
+            }
+        }
+    }
+}
+
+/// Escapes the characters that the buffer's [`EscapePolicy`] escapes beyond
+/// [`EscapePolicy::Minimal`] in `output[start..]`, which must already be
+/// escaped with the minimal policy.
+pub(crate) fn escape_extra(output: &mut Buffer, start: usize) {
+    let policy = output.escape_policy();
+    if policy == EscapePolicy::Minimal {
+        return;
+    }
+
+    output.replace_chars(start, |ch, out| {
+        let esc = policy
+            .escape_char(ch)
+            .filter(|_| escape_char(ch).is_none())?;
+        out[..esc.len()].copy_from_slice(esc.as_bytes());
+        Some(esc.len())
+    });
+}
+
+/// Escapes `& < > "` in `input`, scanning it in chunks for special characters
+/// and copying the runs between them as is.
+#[inline]
+fn escape_minimal(output: &mut Buffer, input: &str) {
+    let bytes = input.as_bytes();
+    let mut last = 0;
+    let mut i = 0;
+
+    // Writes the run before the special character at `j`, and its escaped
+    // form. Special characters are ASCII, so `j` is always a char boundary.
+    macro_rules! escape_at {
+        ($j:expr) => {{
+            let j = $j;
+            if let Some(esc) = escape_byte(bytes[j]) {
+                if last < j {
+                    output.push_str(&input[last..j]);
+                }
+                output.push_str(esc);
+                last = j + 1;
+            }
+        }};
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    {
+        use core::arch::x86_64::*;
+
+        // SAFETY: SSE2 is available, and every load reads 16 bytes within
+        // `bytes`.
+        unsafe {
+            let amp = _mm_set1_epi8(b'&' as i8);
+            let lt = _mm_set1_epi8(b'<' as i8);
+            let gt = _mm_set1_epi8(b'>' as i8);
+            let quot = _mm_set1_epi8(b'"' as i8);
+            while i + 16 <= bytes.len() {
+                let chunk =
+                    _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+                let matches = _mm_or_si128(
+                    _mm_or_si128(
+                        _mm_cmpeq_epi8(chunk, amp),
+                        _mm_cmpeq_epi8(chunk, lt),
+                    ),
+                    _mm_or_si128(
+                        _mm_cmpeq_epi8(chunk, gt),
+                        _mm_cmpeq_epi8(chunk, quot),
+                    ),
+                );
+                let mut mask = _mm_movemask_epi8(matches) as u32;
+                while mask != 0 {
+                    escape_at!(i + mask.trailing_zeros() as usize);
+                    mask &= mask - 1;
+                }
+                i += 16;
+            }
+        }
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        use core::arch::aarch64::*;
+
+        // SAFETY: NEON is available, and every load reads 16 bytes within
+        // `bytes`.
+        unsafe {
+            let amp = vdupq_n_u8(b'&');
+            let lt = vdupq_n_u8(b'<');
+            let gt = vdupq_n_u8(b'>');
+            let quot = vdupq_n_u8(b'"');
+            while i + 16 <= bytes.len() {
+                let chunk = vld1q_u8(bytes.as_ptr().add(i));
+                let matches = vorrq_u8(
+                    vorrq_u8(vceqq_u8(chunk, amp), vceqq_u8(chunk, lt)),
+                    vorrq_u8(vceqq_u8(chunk, gt), vceqq_u8(chunk, quot)),
+                );
+                if vmaxvq_u8(matches) != 0 {
+                    for j in i..i + 16 {
+                        escape_at!(j);
+                    }
+                }
+                i += 16;
+            }
+        }
+    }
+
+    // Scan a word at a time, using the classic "has zero byte" trick on the
+    // word XORed with each special character.
+    const LO: u64 = u64::from_ne_bytes([0x01; 8]);
+    const HI: u64 = u64::from_ne_bytes([0x80; 8]);
+    #[inline]
+    const fn has_byte(word: u64, b: u8) -> bool {
+        let x = word ^ (LO * b as u64);
+        x.wrapping_sub(LO) & !x & HI != 0
+    }
+
+    while i + 8 <= bytes.len() {
+        let mut word = [0; 8];
+        word.copy_from_slice(&bytes[i..i + 8]);
+        let word = u64::from_ne_bytes(word);
+        if has_byte(word, b'&')
+            || has_byte(word, b'<')
+            || has_byte(word, b'>')
+            || has_byte(word, b'"')
+        {
+            for j in i..i + 8 {
+                escape_at!(j);
+            }
+        }
+        i += 8;
+    }
+
+    for j in i..bytes.len() {
+        escape_at!(j);
+    }
+
+    output.push_str(&input[last..]);
+}
+
+/// Returns the length of `input` once escaped with
+/// [`EscapePolicy::Minimal`].
+///
+/// ```
+/// # use vy::escape::escaped_len;
+/// assert_eq!(escaped_len("a < b"), "a &lt; b".len());
+/// ```
+#[inline]
+pub fn escaped_len(input: &str) -> usize {
+    let mut n = input.len();
+    for &b in input.as_bytes() {
+        if let Some(esc) = escape_byte(b) {
+            n += esc.len() - 1;
+        }
     }
+    n
 }
 
 /// Escapes all special HTML characters in `input`.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn escape(input: &str) -> Buffer {
     let mut output = Buffer::with_capacity(input.len());
@@ -33,6 +264,204 @@ pub fn escape(input: &str) -> Buffer {
     output
 }
 
+/// Decodes all named and numeric character references in `input`, following
+/// the rules for text content in HTML.
+///
+/// ```
+/// # use vy::escape::unescape;
+/// assert_eq!(
+///     unescape("Caf&eacute; &amp; &#x1F980; &copy 2025"),
+///     "Café & 🦀 © 2025"
+/// );
+/// ```
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn unescape(input: &str) -> Cow<'_, str> {
+    unescape_with(input, false)
+}
+
+/// Decodes the character references in an attribute value, where legacy
+/// references without a `;` are left alone if followed by an alphanumeric
+/// character or `=`.
+#[cfg(feature = "sanitize")]
+#[inline]
+pub(crate) fn unescape_attr(input: &str) -> Cow<'_, str> {
+    unescape_with(input, true)
+}
+
+#[cfg(feature = "alloc")]
+fn unescape_with(input: &str, attr: bool) -> Cow<'_, str> {
+    if !input.contains('&') {
+        return Cow::Borrowed(input);
+    }
+
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let len = match unescape_reference(rest, attr, &mut out) {
+            0 => {
+                out.push('&');
+                1
+            }
+            len => len,
+        };
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Decodes the character reference at the start of `input` into `out`,
+/// returning the number of bytes consumed, or `0` if there is none.
+#[cfg(feature = "alloc")]
+fn unescape_reference(input: &str, attr: bool, out: &mut String) -> usize {
+    let bytes = input.as_bytes();
+    if bytes.get(1) == Some(&b'#') {
+        let (radix, start) = match bytes.get(2) {
+            Some(b'x' | b'X') => (16, 3),
+            _ => (10, 2),
+        };
+        let digits = bytes[start..]
+            .iter()
+            .take_while(|b| {
+                b.is_ascii_hexdigit() && (radix == 16 || b.is_ascii_digit())
+            })
+            .count();
+        if digits == 0 {
+            return 0;
+        }
+        let code = u32::from_str_radix(&input[start..start + digits], radix)
+            .unwrap_or(u32::MAX);
+        out.push(numeric_reference(code));
+        let len = start + digits;
+        return if bytes.get(len) == Some(&b';') {
+            len + 1
+        } else {
+            len
+        };
+    }
+
+    let name_len = bytes[1..]
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric())
+        .count();
+    if bytes.get(1 + name_len) == Some(&b';') {
+        if let Some(chars) = entities::lookup(&input[1..name_len + 2]) {
+            out.push_str(chars);
+            return name_len + 2;
+        }
+    }
+
+    // Legacy references may omit the `;`, the longest match wins.
+    for len in (2..=name_len.min(entities::LEGACY_MAX_LEN)).rev() {
+        if let Some(chars) = entities::lookup(&input[1..1 + len]) {
+            if attr
+                && bytes
+                    .get(1 + len)
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'=')
+            {
+                return 0;
+            }
+            out.push_str(chars);
+            return 1 + len;
+        }
+    }
+    0
+}
+
+/// Returns the character of a numeric reference, replacing invalid code
+/// points and mapping C1 controls like Windows-1252.
+#[cfg(feature = "alloc")]
+fn numeric_reference(code: u32) -> char {
+    const C1: [u16; 32] = [
+        0x20AC, 0x81, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6,
+        0x2030, 0x0160, 0x2039, 0x0152, 0x8D, 0x017D, 0x8F, 0x90, 0x2018,
+        0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC, 0x2122, 0x0161,
+        0x203A, 0x0153, 0x9D, 0x017E, 0x0178,
+    ];
+    let code = match code {
+        0 => return char::REPLACEMENT_CHARACTER,
+        0x80..=0x9F => C1[(code - 0x80) as usize] as u32,
+        _ => code,
+    };
+    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Returns the total length of `parts`, used by the element macros to
+/// concatenate static HTML at compile time.
+#[doc(hidden)]
+pub const fn concat_len(parts: &[&str]) -> usize {
+    let mut len = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        len += parts[i].len();
+        i += 1;
+    }
+    len
+}
+
+/// Concatenates `parts`, whose total length must be `N`.
+#[doc(hidden)]
+pub const fn concat<const N: usize>(parts: &[&str]) -> [u8; N] {
+    let mut out = [0; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < parts.len() {
+        let bytes = parts[i].as_bytes();
+        let mut j = 0;
+        while j < bytes.len() {
+            out[len] = bytes[j];
+            len += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    assert!(len == N, "length mismatch");
+    out
+}
+
+/// Renders `html` with the given [`EscapePolicy`].
+///
+/// The previous policy is restored afterwards.
+#[inline]
+pub fn with_policy<H: IntoHtml>(
+    policy: EscapePolicy,
+    html: H,
+) -> WithPolicy<H> {
+    WithPolicy { policy, html }
+}
+
+/// An [`IntoHtml`] value created by [`with_policy`].
+pub struct WithPolicy<H> {
+    policy: EscapePolicy,
+    html: H,
+}
+
+impl<H: IntoHtml> IntoHtml for WithPolicy<H> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let prev = buf.set_escape_policy(self.policy);
+        self.html.escape_and_write(buf);
+        buf.set_escape_policy(prev);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.html.size_hint()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.html.exact_size()
+    }
+}
+
 /// A type that requires no further escaping.
 pub struct PreEscaped<T>(pub T);
 
@@ -53,6 +482,7 @@ impl IntoHtml for PreEscaped<&str> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl IntoHtml for PreEscaped<String> {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -70,6 +500,24 @@ impl IntoHtml for PreEscaped<String> {
     }
 }
 
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl IntoHtml for PreEscaped<Arc<str>> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        buf.push_str(&self.0);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+}
+
 impl IntoHtml for PreEscaped<char> {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -87,6 +535,7 @@ impl IntoHtml for PreEscaped<char> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl IntoHtml for PreEscaped<Cow<'static, str>> {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -106,8 +555,142 @@ impl IntoHtml for PreEscaped<Cow<'static, str>> {
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
+    /// The original char-by-char implementation.
+    fn escape_chars(input: &str) -> String {
+        let mut output = String::new();
+        for ch in input.chars() {
+            match escape_char(ch) {
+                Some(esc) => output.push_str(esc),
+                _ => output.push(ch),
+            };
+        }
+        output
+    }
+
+    #[test]
+    fn escape_runs() {
+        assert_eq!(escape("").as_str(), "");
+        assert_eq!(escape("plain text").as_str(), "plain text");
+        assert_eq!(
+            escape("<a href=\"x&y\">").as_str(),
+            "&lt;a href=&quot;x&amp;y&quot;&gt;"
+        );
+        // Special characters at every position around the 8 and 16 byte
+        // chunk boundaries.
+        for len in 0..40 {
+            for pos in 0..=len {
+                let mut input = "x".repeat(len);
+                input.insert(pos, '&');
+                assert_eq!(escape(&input).as_str(), escape_chars(&input));
+                input.insert(0, 'é');
+                input.push('"');
+                assert_eq!(escape(&input).as_str(), escape_chars(&input));
+            }
+        }
+    }
+
+    fn escape_with(policy: EscapePolicy, input: &str) -> String {
+        let mut buf = Buffer::new();
+        buf.set_escape_policy(policy);
+        escape_into(&mut buf, input);
+        buf.into_string()
+    }
+
+    #[test]
+    fn unescape_references() {
+        assert_eq!(unescape("a &amp; b"), "a & b");
+        assert_eq!(unescape("&#60;&#x3C;&#X3c"), "<<<");
+        assert_eq!(unescape("javascript&colon;"), "javascript:");
+        assert_eq!(unescape("&unknown; &amp &ampx"), "&unknown; & &x");
+        assert_eq!(unescape("&notit; &notin;"), "¬it; ∉");
+        assert_eq!(unescape("&acE; &NotEqualTilde;"), "∾\u{333} ≂\u{338}");
+        assert_eq!(
+            unescape("&#0;&#xD800;&#x110000;&#99999999999;"),
+            "\u{fffd}\u{fffd}\u{fffd}\u{fffd}"
+        );
+        assert_eq!(unescape("&#128;&#x9f;"), "€Ÿ");
+        assert_eq!(unescape("& &# &#x; &;"), "& &# &#x; &;");
+    }
+
+    #[cfg(feature = "sanitize")]
+    #[test]
+    fn unescape_attr_references() {
+        assert_eq!(unescape_attr("?a=1&copy=2&not;"), "?a=1&copy=2¬");
+        assert_eq!(unescape_attr("&copy2 &copy"), "&copy2 ©");
+    }
+
+    proptest! {
+        #[test]
+        fn unescape_roundtrip(input in "\\PC*") {
+            let escaped = escape(&input);
+            prop_assert_eq!(unescape(escaped.as_str()), input);
+        }
+    }
+
+    #[test]
+    fn policies() {
+        let input = "<a href='/x?y=`z`'>&\"\u{1}\t";
+        assert_eq!(
+            escape_with(EscapePolicy::Minimal, input),
+            "&lt;a href='/x?y=`z`'&gt;&amp;&quot;\u{1}\t"
+        );
+        assert_eq!(
+            escape_with(EscapePolicy::Strict, input),
+            "&lt;a href&#x3D;&#x27;&#x2F;x?y&#x3D;&#x60;z&#x60;&#x27;&gt;&amp;\
+             &quot;\u{1}\t"
+        );
+        assert_eq!(
+            escape_with(EscapePolicy::Xml, input),
+            "&lt;a href=&#39;/x?y=`z`&#39;&gt;&amp;&quot;\t"
+        );
+        assert_eq!(escape_with(EscapePolicy::Xml, "a\u{FFFF}\u{0}b"), "ab");
+    }
+
+    #[test]
+    fn extra() {
+        let mut buf = Buffer::new();
+        buf.set_escape_policy(EscapePolicy::Strict);
+        buf.push_str("a=b");
+        let start = buf.len();
+        buf.push_str("&amp;/'");
+        escape_extra(&mut buf, start);
+        assert_eq!(buf.as_str(), "a=b&amp;&#x2F;&#x27;");
+    }
+
+    #[test]
+    fn scoped_policy() {
+        let html = (
+            "'",
+            with_policy(
+                EscapePolicy::Strict,
+                ("'", with_policy(EscapePolicy::Xml, "'"), "'"),
+            ),
+            "'",
+        );
+        assert_eq!(html.into_string(), "'&#x27;&#39;&#x27;'");
+    }
+
+    proptest! {
+        #[test]
+        fn matches_char_by_char(input in "\\PC*") {
+            prop_assert_eq!(escape(&input).into_string(), escape_chars(&input));
+        }
+
+        #[test]
+        fn matches_char_by_char_html(input in "[a-z<>&\"' é\n]{0,100}") {
+            prop_assert_eq!(escape(&input).into_string(), escape_chars(&input));
+        }
+
+        #[test]
+        fn exact_escaped_len(input in "[a-z<>&\"' é\n]{0,100}") {
+            prop_assert_eq!(escaped_len(&input), escape(&input).len());
+        }
+    }
+
     #[test]
     fn test_preescaped_cow_str() {
         assert_eq!(
@@ -0,0 +1,439 @@
+//! Entity tags for rendered pages.
+//!
+//! [`render`] renders a template and hashes its output with a
+//! [`ContentHasher`] in one step, returning the body along with a strong
+//! [`ETag`]. A client that already has the page sends the tag back in the
+//! `If-None-Match` header, and the server can answer with `304 Not Modified`
+//! instead of sending the body again:
+//!
+//! ```
+//! # use vy::{etag::*, prelude::*};
+//! fn respond(name: &str, if_none_match: Option<&str>) -> (u16, String) {
+//!     let page = render::<Fnv1a>(h1!("Hello, ", name)).unwrap();
+//!     if page.is_fresh(if_none_match) {
+//!         return (304, String::new());
+//!     }
+//!     (200, page.body)
+//! }
+//!
+//! let page = render::<Fnv1a>(h1!("Hello, ", "Ann")).unwrap();
+//! assert_eq!(page.etag.as_str(), "\"28034d89d978c633\"");
+//! assert_eq!(respond("Ann", Some(page.etag.as_str())).0, 304);
+//! assert_eq!(respond("Bob", Some(page.etag.as_str())).0, 200);
+//! ```
+//!
+//! The output is hashed while it is rendered, a few kilobytes at a time, so
+//! that it is still in the cache when it is hashed. Output that may still be
+//! rewritten, such as a URL attribute that is checked once it is written, is
+//! hashed when it is complete.
+//!
+//! FNV-1a is always available. The `xxhash` feature adds [`Xxh3`], which is
+//! faster for large pages, and the `sha2` feature adds [`Sha256`], for tags
+//! that must not be forgeable.
+
+use alloc::{boxed::Box, string::String};
+use core::{any::Any, cmp, fmt, iter, mem};
+
+use crate::{buffer::Filter, Buffer, Error, IntoHtml};
+
+/// The length of output hashed at a time while rendering.
+const CHUNK: usize = 4096;
+
+/// An incremental hash function for computing entity tags.
+pub trait ContentHasher: Default {
+    /// The output of the hash function.
+    type Digest: AsRef<[u8]>;
+
+    /// Feeds `bytes` into the hasher.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Returns the digest of all bytes fed into the hasher.
+    fn finish(self) -> Self::Digest;
+}
+
+/// The 64-bit FNV-1a hash function.
+#[derive(Clone, Copy, Debug)]
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    #[inline]
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl ContentHasher for Fnv1a {
+    type Digest = [u8; 8];
+
+    #[inline]
+    fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    #[inline]
+    fn finish(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+}
+
+/// The 128-bit XXH3 hash function.
+#[cfg(feature = "xxhash")]
+#[derive(Clone, Default)]
+pub struct Xxh3(xxhash_rust::xxh3::Xxh3Default);
+
+#[cfg(feature = "xxhash")]
+impl ContentHasher for Xxh3 {
+    type Digest = [u8; 16];
+
+    #[inline]
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    #[inline]
+    fn finish(self) -> [u8; 16] {
+        self.0.digest128().to_be_bytes()
+    }
+}
+
+/// The SHA-256 hash function.
+#[cfg(feature = "sha2")]
+#[derive(Clone, Default)]
+pub struct Sha256(sha2::Sha256);
+
+#[cfg(feature = "sha2")]
+impl ContentHasher for Sha256 {
+    type Digest = [u8; 32];
+
+    #[inline]
+    fn update(&mut self, bytes: &[u8]) {
+        sha2::Digest::update(&mut self.0, bytes);
+    }
+
+    #[inline]
+    fn finish(self) -> [u8; 32] {
+        sha2::Digest::finalize(self.0).into()
+    }
+}
+
+/// A strong entity tag, such as `"28034d89d978c633"`, including the quotes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ETag(String);
+
+impl ETag {
+    /// Hashes `bytes` with `H` and returns the tag of the digest.
+    pub fn new<H: ContentHasher>(bytes: &[u8]) -> Self {
+        let mut hasher = H::default();
+        hasher.update(bytes);
+        Self::from_digest(hasher.finish().as_ref())
+    }
+
+    /// Returns the tag of a digest, in lowercase hexadecimal.
+    pub fn from_digest(digest: &[u8]) -> Self {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        let mut tag = String::with_capacity(digest.len() * 2 + 2);
+        tag.push('"');
+        for &b in digest {
+            tag.push(HEX[(b >> 4) as usize] as char);
+            tag.push(HEX[(b & 0xf) as usize] as char);
+        }
+        tag.push('"');
+        Self(tag)
+    }
+
+    /// Returns the tag as a header value.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if the value of an `If-None-Match` header lists this
+    /// tag, or is `*`.
+    ///
+    /// Tags are compared with the weak comparison that the header calls for,
+    /// so `W/"28034d89d978c633"` matches as well.
+    pub fn matches(&self, if_none_match: &str) -> bool {
+        if if_none_match.trim() == "*" {
+            return true;
+        }
+        entity_tags(if_none_match).any(|tag| tag == self.0)
+    }
+}
+
+impl fmt::Display for ETag {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<ETag> for String {
+    #[inline]
+    fn from(etag: ETag) -> String {
+        etag.0
+    }
+}
+
+/// A rendered page with its entity tag, created by [`render`].
+#[derive(Clone, Debug)]
+pub struct Tagged {
+    /// The rendered HTML.
+    pub body: String,
+    /// The tag of the HTML.
+    pub etag: ETag,
+}
+
+impl Tagged {
+    /// Returns `true` if the client already has this page, according to the
+    /// value of its `If-None-Match` header, so that the server can respond
+    /// with `304 Not Modified`.
+    #[inline]
+    pub fn is_fresh(&self, if_none_match: Option<&str>) -> bool {
+        if_none_match.is_some_and(|header| self.etag.matches(header))
+    }
+}
+
+/// Renders `html` and computes the entity tag of the output with `H` as it
+/// is written, or returns the first error encountered while rendering.
+pub fn render<H>(html: impl IntoHtml) -> Result<Tagged, Error>
+where
+    H: ContentHasher + 'static,
+{
+    let mut buf = Buffer::new();
+    let writer = HashWriter::new(H::default());
+    buf.set_filter(Some(Filter::Hash(Box::new(writer))));
+    html.render_into(&mut buf);
+    let filter = buf.set_filter(None);
+    let body = buf.try_into_string()?;
+    let etag = match filter {
+        Some(Filter::Hash(writer)) => writer.finish::<H>(body.as_bytes()),
+        _ => ETag::new::<H>(body.as_bytes()),
+    };
+    Ok(Tagged { body, etag })
+}
+
+/// A [`ContentHasher`] of any type.
+trait AnyHasher {
+    fn update(&mut self, bytes: &[u8]);
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<H: ContentHasher + 'static> AnyHasher for H {
+    #[inline]
+    fn update(&mut self, bytes: &[u8]) {
+        ContentHasher::update(self, bytes);
+    }
+
+    #[inline]
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Hashes the output of a buffer as it is written.
+pub(crate) struct HashWriter {
+    hasher: Box<dyn AnyHasher>,
+    /// The length of the output fed into the hasher.
+    hashed: usize,
+    /// The start of the output that may still be rewritten, which is not
+    /// hashed yet.
+    hold: usize,
+    /// Whether output that was already hashed was rewritten, in which case
+    /// the whole output is hashed again once rendering has finished.
+    dirty: bool,
+}
+
+impl HashWriter {
+    fn new<H: ContentHasher + 'static>(hasher: H) -> Self {
+        Self {
+            hasher: Box::new(hasher),
+            hashed: 0,
+            hold: usize::MAX,
+            dirty: false,
+        }
+    }
+
+    /// Writes `html`, hashing the output written so far once a chunk of it
+    /// is complete.
+    pub(crate) fn write(&mut self, buf: &mut Buffer, html: &str) {
+        buf.push_str(html);
+        if buf.len().saturating_sub(self.hashed) >= CHUNK {
+            self.flush(buf.as_bytes());
+        }
+    }
+
+    /// Hashes the output up to the held part.
+    fn flush(&mut self, output: &[u8]) {
+        let end = cmp::min(output.len(), self.hold);
+        if !self.dirty && end > self.hashed {
+            self.hasher.update(&output[self.hashed..end]);
+            self.hashed = end;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn hold(&mut self, start: usize) -> usize {
+        let hold = cmp::min(self.hold, start);
+        mem::replace(&mut self.hold, hold)
+    }
+
+    #[inline]
+    pub(crate) fn release(&mut self, hold: usize) {
+        self.hold = hold;
+    }
+
+    #[inline]
+    pub(crate) fn rewind(&mut self, start: usize) {
+        if start < self.hashed {
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the tag of the whole `output`.
+    fn finish<H: ContentHasher + 'static>(mut self, output: &[u8]) -> ETag {
+        if self.dirty {
+            return ETag::new::<H>(output);
+        }
+        self.hold = usize::MAX;
+        self.flush(output);
+        match self.hasher.into_any().downcast::<H>() {
+            Ok(hasher) => ETag::from_digest(hasher.finish().as_ref()),
+            Err(_) => ETag::new::<H>(output),
+        }
+    }
+}
+
+/// Returns the opaque tags, including quotes, of a list of entity tags,
+/// stopping at the first malformed one.
+fn entity_tags(header: &str) -> impl Iterator<Item = &str> {
+    let mut rest = header;
+    iter::from_fn(move || {
+        rest = rest.trim_start_matches([' ', '\t', ',']);
+        let tag = rest.strip_prefix("W/").unwrap_or(rest);
+        let len = tag.strip_prefix('"')?.find('"')? + 2;
+        rest = &tag[len..];
+        Some(&tag[..len])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a() {
+        assert_eq!(ETag::new::<Fnv1a>(b"").as_str(), "\"cbf29ce484222325\"");
+        assert_eq!(ETag::new::<Fnv1a>(b"a").as_str(), "\"af63dc4c8601ec8c\"");
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn xxh3() {
+        let etag = ETag::new::<Xxh3>(b"");
+        assert_eq!(etag.as_str(), "\"99aa06d3014798d86001c324468d497f\"");
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            ETag::new::<Sha256>(b"abc").as_str(),
+            "\"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\""
+        );
+    }
+
+    #[test]
+    fn incremental() {
+        use crate::{
+            escape::{with_policy, EscapePolicy},
+            url::UrlAttr,
+        };
+
+        let hashed = |html: &dyn Fn(&mut Buffer)| {
+            let mut buf = Buffer::new();
+            let writer = HashWriter::new(Fnv1a::default());
+            buf.set_filter(Some(Filter::Hash(Box::new(writer))));
+            html(&mut buf);
+            let Some(Filter::Hash(writer)) = buf.set_filter(None) else {
+                unreachable!();
+            };
+            (buf.into_string(), writer)
+        };
+
+        let long = "x".repeat(CHUNK);
+        let (body, writer) = hashed(&|buf| {
+            let url = UrlAttr((long.as_str(), " /a b'"));
+            with_policy(EscapePolicy::Strict, (long.as_str(), url))
+                .render_into(buf);
+        });
+        assert!(body.ends_with("x%20&#x2F;a%20b&#x27;"));
+        assert!(!writer.dirty);
+        assert_eq!(writer.hashed, CHUNK);
+        let etag = writer.finish::<Fnv1a>(body.as_bytes());
+        assert_eq!(etag, ETag::new::<Fnv1a>(body.as_bytes()));
+
+        let (body, writer) = hashed(&|buf| {
+            buf.push_str(&long);
+            buf.push_str("a");
+            buf.truncate(1);
+            buf.push_str("b");
+        });
+        assert!(writer.dirty);
+        let etag = writer.finish::<Fnv1a>(body.as_bytes());
+        assert_eq!(etag, ETag::new::<Fnv1a>(b"xb"));
+    }
+
+    #[test]
+    fn filter_is_dropped() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Default)]
+        struct Dropped;
+
+        impl ContentHasher for Dropped {
+            type Digest = [u8; 0];
+
+            fn update(&mut self, _bytes: &[u8]) {}
+
+            fn finish(self) -> [u8; 0] {
+                []
+            }
+        }
+
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let mut buf = Buffer::new();
+        let writer = HashWriter::new(Dropped);
+        buf.set_filter(Some(Filter::Hash(Box::new(writer))));
+        buf.push_str("<p>");
+        assert_eq!(buf.into_string(), "<p>");
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn render_and_match() {
+        let page = render::<Fnv1a>(("<", 1)).unwrap();
+        assert_eq!(page.body, "&lt;1");
+        assert_eq!(page.etag, ETag::new::<Fnv1a>(b"&lt;1"));
+        assert!(render::<Fnv1a>(Err::<&str, _>(Error::msg("failed"))).is_err());
+
+        let tag = page.etag.as_str();
+        assert!(page.is_fresh(Some(tag)));
+        assert!(page.is_fresh(Some(&alloc::format!("\"x\", W/{tag}"))));
+        assert!(page.is_fresh(Some(" * ")));
+        assert!(!page.is_fresh(None));
+        assert!(!page.is_fresh(Some("\"x\"")));
+        assert!(!page.is_fresh(Some(&alloc::format!("x, {tag}"))));
+        assert!(!page.is_fresh(Some(&tag[1..])));
+    }
+}
@@ -0,0 +1,214 @@
+//! Rendering a single fragment of a page.
+//!
+//! Libraries like htmx update part of a page with HTML from the server. The
+//! `fragment!` macro names a part of a template, and [`render`] renders only
+//! that part, so that the full page and the partial response share one
+//! template:
+//!
+//! ```
+//! # use vy::{fragment, prelude::*};
+//! fn cart(items: usize) -> impl IntoHtml {
+//!     fragment!("cart", div!(id = "cart", items, " items"))
+//! }
+//!
+//! fn page(items: usize) -> impl IntoHtml {
+//!     body!(h1!("Shop"), cart(items), footer!("..."))
+//! }
+//!
+//! assert_eq!(
+//!     page(2).into_string(),
+//!     "<body><h1>Shop</h1><div id=\"cart\">2 \
+//!      items</div><footer>...</footer></body>"
+//! );
+//! assert_eq!(
+//!     fragment::render(page(2), "cart").unwrap(),
+//!     "<div id=\"cart\">2 items</div>"
+//! );
+//! ```
+//!
+//! Outside of [`render`], fragments render their content as usual. Within
+//! it, the output preceding the named fragment is discarded as it is
+//! written, and rendering stops once the fragment is complete. Values
+//! preceding it are still evaluated, so keep expensive work inside of
+//! fragments. Fragments are also rendered in place of
+//! [cached](crate::cache) HTML, which could contain them.
+
+use alloc::string::String;
+use core::{error::Error as StdError, fmt};
+
+use crate::{buffer::Filter, Buffer, Error, IntoHtml};
+
+/// The error returned by [`render`] when the page has no fragment of the
+/// given name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NotFound(pub String);
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fragment `{}` not found", self.0)
+    }
+}
+
+impl StdError for NotFound {}
+
+/// Recorded as an error once the fragment is rendered, which stops the
+/// rendering of the rest of the page.
+#[derive(Debug)]
+struct Done;
+
+impl fmt::Display for Done {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("fragment rendered")
+    }
+}
+
+impl StdError for Done {}
+
+/// The fragment being rendered by [`render`], kept in the render context.
+struct Target {
+    name: String,
+    found: bool,
+}
+
+/// Renders only the fragment of `html` named `name`, or returns the first
+/// error encountered while rendering.
+///
+/// Returns a [`NotFound`] error if `html` has no such fragment.
+pub fn render(html: impl IntoHtml, name: &str) -> Result<String, Error> {
+    let mut buf = Buffer::new();
+    buf.provide(Target {
+        name: name.into(),
+        found: false,
+    });
+    buf.set_filter(Some(Filter::Discard));
+    html.render_into(&mut buf);
+    buf.set_filter(None);
+
+    let found = buf
+        .context_mut()
+        .remove::<Target>()
+        .is_some_and(|target| target.found);
+    match buf.take_error() {
+        Some(error) if error.downcast_ref::<Done>().is_none() => Err(error),
+        _ if !found => Err(Error::new(NotFound(name.into()))),
+        _ => buf.try_into_string(),
+    }
+}
+
+/// Returns `true` if `buf` is rendering a single fragment.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn is_partial(buf: &Buffer) -> bool {
+    buf.use_context::<Target>().is_some()
+}
+
+/// A named part of a template, created by the `fragment!` macro.
+pub struct Fragment<N, H> {
+    name: N,
+    html: H,
+}
+
+impl<N: AsRef<str>, H: IntoHtml> Fragment<N, H> {
+    /// Creates a fragment named `name`.
+    #[inline]
+    pub const fn new(name: N, html: H) -> Self {
+        Self { name, html }
+    }
+}
+
+impl<N: AsRef<str>, H: IntoHtml> IntoHtml for Fragment<N, H> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let Some(target) = buf.context().get::<Target>() else {
+            self.html.escape_and_write(buf);
+            return;
+        };
+        if target.found {
+            return;
+        }
+        // Other fragments may still contain the target.
+        if target.name != self.name.as_ref() {
+            self.html.escape_and_write(buf);
+            return;
+        }
+
+        let filter = buf.set_filter(None);
+        self.html.escape_and_write(buf);
+        buf.set_filter(filter);
+        if let Some(target) = buf.context_mut().get_mut::<Target>() {
+            target.found = true;
+        }
+        buf.set_error(Done);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.html.size_hint()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.html.exact_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn render_fragment() {
+        let page = |name| {
+            (
+                "<a>",
+                Fragment::new("outer", ("<b>", Fragment::new(name, "<c>"))),
+                Fragment::new("c", 1u8),
+            )
+        };
+
+        assert_eq!(page("c").into_string(), "&lt;a&gt;&lt;b&gt;&lt;c&gt;1");
+        assert_eq!(render(page("c"), "outer").unwrap(), "&lt;b&gt;&lt;c&gt;");
+        assert_eq!(render(page("c"), "c").unwrap(), "&lt;c&gt;");
+        assert_eq!(render(page("d"), "c").unwrap(), "1");
+
+        let error = render(page("c"), "d").unwrap_err();
+        assert_eq!(error.to_string(), "fragment `d` not found");
+        let failed = ("a", Err::<&str, _>(Error::msg("failed")));
+        assert!(render(failed, "a").is_err());
+        let failed = Fragment::new("a", Err::<&str, _>(Error::msg("failed")));
+        assert!(render(failed, "a").is_err());
+    }
+
+    #[test]
+    fn skips_the_rest_of_the_page() {
+        struct Counted<'a>(&'a core::cell::Cell<u32>);
+
+        impl IntoHtml for Counted<'_> {
+            fn into_html(self) -> impl IntoHtml {
+                self
+            }
+
+            fn escape_and_write(self, buf: &mut Buffer) {
+                self.0.set(self.0.get() + 1);
+                buf.push_str("<i>");
+            }
+        }
+
+        let rendered = core::cell::Cell::new(0);
+        let count = || Counted(&rendered);
+        let page = (
+            [count(), count()],
+            Fragment::new("b", ("<b>", count())),
+            [count(), count()],
+            count(),
+        );
+        assert_eq!(render(page, "b").unwrap(), "&lt;b&gt;<i>");
+        assert_eq!(rendered.get(), 3);
+    }
+}
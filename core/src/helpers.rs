@@ -1,5 +1,18 @@
+use core::fmt;
+
 use crate::{buffer::Buffer, IntoHtml};
 
+/// Counts the bytes written to it.
+pub(crate) struct Counter(pub usize);
+
+impl fmt::Write for Counter {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
 macro_rules! impl_tuple {
 	( ( $($i:ident,)+ ) ) => {
 		impl<$($i,)+> IntoHtml for ($($i,)+)
@@ -20,6 +33,9 @@ macro_rules! impl_tuple {
 				#[allow(non_snake_case)]
 				let ($($i,)+) = self;
 				$(
+					if buf.error().is_some() {
+						return;
+					}
 					$i.escape_and_write(buf);
 				)+
 			}
@@ -34,6 +50,17 @@ macro_rules! impl_tuple {
 				)+
                 n
             }
+
+            #[inline]
+            fn exact_size(&self) -> usize {
+				#[allow(non_snake_case)]
+				let ($($i,)+) = self;
+                let mut n = 0;
+				$(
+					n += $i.exact_size();
+				)+
+                n
+            }
 		}
 	};
 	($f:ident) => {
@@ -62,10 +89,21 @@ macro_rules! via_itoa {
 
                     use itoap::Integer;
 
+                    if buf.is_filtered()
+                        || buf.capacity() - buf.len() < Self::MAX_LEN
+                            && !buf.grow(Self::MAX_LEN)
+                    {
+                        // The buffer is close to its maximum length, but may
+                        // still have room for the digits, or the digits must
+                        // go through its filter.
+                        _ = fmt::Write::write_fmt(buf, format_args!("{self}"));
+                        return;
+                    }
+
                     // SAFETY: `MAX_LEN < 40` and then does not overflows `isize::MAX`.
                     // Also `b.len()` should be always less than or equal to `isize::MAX`.
+                    // The capacity was checked above.
                     unsafe {
-                        buf.reserve_small(Self::MAX_LEN);
                         let ptr = buf.as_mut_ptr().add(buf.len());
 
                         // SAFETY: `MAX_LEN` is always greater than zero, so
@@ -75,13 +113,23 @@ macro_rules! via_itoa {
                     }
                     debug_assert!(buf.len() <= buf.capacity());
                 }
+
+                #[inline]
+                #[allow(unused_comparisons)]
+                fn size_hint(&self) -> usize {
+                    let digits = self
+                        .abs_diff(0)
+                        .checked_ilog10()
+                        .map_or(1, |n| n as usize + 1);
+                    digits + (*self < 0) as usize
+                }
             }
         )*
     };
 }
 
 macro_rules! via_ryu {
-    ($($ty:ty)*) => {
+    ($($ty:ty => $max_len:expr)*) => {
         $(
             impl $crate::IntoHtml for $ty {
                 #[inline]
@@ -93,6 +141,16 @@ macro_rules! via_ryu {
                 fn escape_and_write(self, buf: &mut Buffer) {
                     buf.push_str(ryu::Buffer::new().format(self));
                 }
+
+                #[inline]
+                fn size_hint(&self) -> usize {
+                    $max_len
+                }
+
+                #[inline]
+                fn exact_size(&self) -> usize {
+                    ryu::Buffer::new().format(*self).len()
+                }
             }
         )*
     };
@@ -103,4 +161,4 @@ via_itoa! {
     usize u8 u16 u32 u64 u128
 }
 
-via_ryu! { f32 f64 }
+via_ryu! { f32 => 16 f64 => 24 }
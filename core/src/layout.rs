@@ -0,0 +1,77 @@
+//! Layouts with named slots.
+//!
+//! The `layout!` macro declares a layout: a struct with one field per slot,
+//! each with default content, and an [`IntoHtml`] implementation that renders
+//! the layout around them. Pages fill the slots they need with the method of
+//! the same name, and the others render their default content:
+//!
+//! ```
+//! # use vy::prelude::*;
+//! layout! {
+//!     /// The base layout of the site.
+//!     pub struct Base {
+//!         title = "vy",
+//!         head_extra = (),
+//!         content = p!("Nothing here yet."),
+//!         scripts = (),
+//!     } => (
+//!         DOCTYPE,
+//!         html!(
+//!             head!(title!(title), head_extra),
+//!             body!(main!(content), scripts)
+//!         ),
+//!     )
+//! }
+//!
+//! fn home(name: &str) -> impl IntoHtml + '_ {
+//!     Base::new()
+//!         .title("Home")
+//!         .content((h1!("Hello, ", name), p!("Welcome back.")))
+//! }
+//!
+//! assert_eq!(
+//!     home("Ann").into_string(),
+//!     "<!DOCTYPE html><html><head><title>Home</title></\
+//!      head><body><main><h1>Hello, Ann</h1><p>Welcome \
+//!      back.</p></main></body></html>"
+//! );
+//! ```
+//!
+//! Filling a slot that the layout does not declare is a compile error. Since
+//! a layout is an [`IntoHtml`] value itself, layouts can fill the slots of
+//! other layouts, much like `{% extends %}` in other template engines.
+
+use crate::IntoHtml;
+
+/// The content of a slot that was not filled, which renders the default
+/// content of the slot.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unset;
+
+/// The content of a slot of a layout, either [`Unset`] or an [`IntoHtml`]
+/// value.
+pub trait Slot {
+    /// The HTML of the slot when its default content is `D`.
+    type Output<D: IntoHtml>: IntoHtml;
+
+    /// Returns the content of the slot, or `default()` if it is [`Unset`].
+    fn fill<D: IntoHtml>(self, default: impl FnOnce() -> D) -> Self::Output<D>;
+}
+
+impl Slot for Unset {
+    type Output<D: IntoHtml> = D;
+
+    #[inline]
+    fn fill<D: IntoHtml>(self, default: impl FnOnce() -> D) -> D {
+        default()
+    }
+}
+
+impl<T: IntoHtml> Slot for T {
+    type Output<D: IntoHtml> = T;
+
+    #[inline]
+    fn fill<D: IntoHtml>(self, _: impl FnOnce() -> D) -> T {
+        self
+    }
+}
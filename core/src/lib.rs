@@ -1,18 +1,54 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
 mod buffer;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "alloc")]
+pub mod context;
+pub mod csp;
 pub mod either;
+#[cfg(feature = "alloc")]
+mod entities;
+mod error;
 pub mod escape;
+#[cfg(feature = "alloc")]
+pub mod etag;
+#[cfg(feature = "alloc")]
+pub mod fragment;
 mod helpers;
-
+pub mod layout;
+pub mod limit;
+#[cfg(feature = "alloc")]
+pub mod markup;
+#[cfg(feature = "std")]
+pub mod pool;
+pub mod pretty;
+pub mod route;
+#[cfg(feature = "sanitize")]
+pub mod sanitize;
+#[cfg(feature = "alloc")]
+pub mod text;
+#[cfg(feature = "sanitize")]
+mod tokenizer;
+pub mod url;
+
+#[cfg(feature = "alloc")]
 use alloc::string::String;
+use core::{cmp, fmt::Write, str};
 
-pub use self::buffer::Buffer;
-use self::escape::escape_into;
+#[cfg(feature = "alloc")]
+pub use self::markup::Markup;
+pub use self::{buffer::Buffer, error::Error, route::Route};
+use self::{
+    buffer::SliceGuard,
+    escape::{escape_into, escaped_len},
+    helpers::Counter,
+};
 
 /// A type that can be represented as HTML.
 pub trait IntoHtml {
@@ -100,22 +136,200 @@ pub trait IntoHtml {
         self.into_html().escape_and_write(buf);
     }
 
+    /// Returns an estimate of the length of the HTML, used to reserve space
+    /// before rendering.
     #[inline]
     fn size_hint(&self) -> usize {
         0
     }
 
+    /// Returns the exact length of the HTML, at the cost of scanning text for
+    /// characters that need escaping.
+    ///
+    /// The length assumes the [`Minimal`](escape::EscapePolicy::Minimal)
+    /// escape policy. Values whose output is only known once they are
+    /// rendered, such as closures and iterators, return their
+    /// [`size_hint`](IntoHtml::size_hint).
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.size_hint()
+    }
+
+    /// Renders the HTML into an existing [`Buffer`], reserving space for it
+    /// up front.
+    ///
+    /// Use this to render with a configured buffer, for example one carrying
+    /// values in its [`Context`](context::Context).
+    fn render_into(self, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let html = self.into_html();
+        let size = html.size_hint();
+        buf.reserve(size + (size / 10));
+        html.escape_and_write(buf);
+    }
+
+    /// Renders the HTML into `bytes` without allocating, returning the
+    /// rendered part as a string slice.
+    ///
+    /// Returns a [`LimitExceeded::Length`](limit::LimitExceeded::Length)
+    /// error if the HTML does not fit, or the first error encountered while
+    /// rendering.
+    ///
+    /// ```
+    /// # use vy::prelude::*;
+    /// let mut bytes = [0; 32];
+    /// let html = || li!("Temperature: ", 21.5, "°C");
+    /// assert_eq!(
+    ///     html().render_to_slice(&mut bytes).unwrap(),
+    ///     "<li>Temperature: 21.5°C</li>"
+    /// );
+    /// assert!(ul!(html()).render_to_slice(&mut bytes).is_err());
+    /// ```
+    fn render_to_slice(self, bytes: &mut [u8]) -> Result<&str, Error>
+    where
+        Self: Sized,
+    {
+        // SAFETY: `bytes` is borrowed until the guard is dropped.
+        let mut guard =
+            unsafe { SliceGuard::new(bytes.as_mut_ptr(), bytes.len()) };
+        self.render_into(guard.buf());
+        if let Some(error) = guard.buf().take_error() {
+            return Err(error);
+        }
+        let len = guard.buf().len();
+        drop(guard);
+        // SAFETY: the buffer wrote `len` bytes of UTF-8 into `bytes`.
+        Ok(unsafe { str::from_utf8_unchecked(&bytes[..len]) })
+    }
+
+    #[cfg(feature = "heapless")]
+    /// Appends the HTML to a [`heapless::String`] without allocating.
+    ///
+    /// Returns a [`LimitExceeded::Length`](limit::LimitExceeded::Length)
+    /// error if the HTML does not fit in the remaining capacity, or the first
+    /// error encountered while rendering, in which case `string` is left
+    /// unchanged.
+    ///
+    /// ```
+    /// # use vy::prelude::*;
+    /// let mut page = heapless::String::<64>::new();
+    /// p!("Uptime: ", 42, "s")
+    ///     .render_to_heapless(&mut page)
+    ///     .unwrap();
+    /// assert_eq!(page, "<p>Uptime: 42s</p>");
+    /// ```
+    fn render_to_heapless<const N: usize>(
+        self,
+        string: &mut heapless::String<N>,
+    ) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        // SAFETY: the spare capacity of the string is borrowed until the
+        // guard is dropped, and only UTF-8 is written into it.
+        unsafe {
+            let vec = string.as_mut_vec();
+            let len = vec.len();
+            let mut guard = SliceGuard::new(vec.as_mut_ptr().add(len), N - len);
+            self.render_into(guard.buf());
+            if let Some(error) = guard.buf().take_error() {
+                return Err(error);
+            }
+            let written = guard.buf().len();
+            drop(guard);
+            vec.set_len(len + written);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc")]
     /// Allocates a new [`String`] containing the HTML.
     fn into_string(self) -> String
+    where
+        Self: Sized,
+    {
+        let mut buf = Buffer::new();
+        self.render_into(&mut buf);
+        buf.into_string()
+    }
+
+    #[cfg(feature = "alloc")]
+    /// Allocates a new [`String`] containing the HTML, measuring it first with
+    /// [`exact_size`](IntoHtml::exact_size) to allocate exactly once.
+    ///
+    /// This trades an extra pass over the text of the template for fewer
+    /// allocations, which pays off for large pages with many escaped values.
+    fn into_string_exact(self) -> String
     where
         Self: Sized,
     {
         let html = self.into_html();
-        let size = html.size_hint();
-        let mut buf = Buffer::with_capacity(size + (size / 10));
+        let mut buf = Buffer::with_capacity(html.exact_size());
         html.escape_and_write(&mut buf);
         buf.into_string()
     }
+
+    #[cfg(feature = "alloc")]
+    /// Allocates a new [`String`] containing the HTML, pretty-printed with
+    /// one block element per line.
+    ///
+    /// See the [`pretty`] module for details.
+    fn into_pretty_string(self) -> String
+    where
+        Self: Sized,
+    {
+        pretty::render(self)
+    }
+
+    #[cfg(feature = "alloc")]
+    /// Allocates a new [`String`] containing the text content of the HTML.
+    ///
+    /// See [`TextOptions`](text::TextOptions) for other options.
+    fn into_text(self) -> String
+    where
+        Self: Sized,
+    {
+        text::TextOptions::new().render(self)
+    }
+
+    #[cfg(feature = "alloc")]
+    /// Allocates a new [`String`] containing the HTML, or returns the first
+    /// error encountered while rendering.
+    ///
+    /// Errors are produced by rendering an [`Err`] value, or by calling
+    /// [`Buffer::set_error`]. On failure, any partial output is discarded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vy::*;
+    /// fn lookup(id: u32) -> Result<&'static str, Error> {
+    ///     match id {
+    ///         1 => Ok("Alice"),
+    ///         _ => Err(Error::msg("user not found")),
+    ///     }
+    /// }
+    ///
+    /// fn profile(id: u32) -> impl IntoHtml {
+    ///     div!(class = "profile", lookup(id))
+    /// }
+    ///
+    /// assert_eq!(
+    ///     profile(1).try_into_string().unwrap(),
+    ///     "<div class=\"profile\">Alice</div>"
+    /// );
+    /// assert!(profile(2).try_into_string().is_err());
+    /// ```
+    fn try_into_string(self) -> Result<String, Error>
+    where
+        Self: Sized,
+    {
+        let mut buf = Buffer::new();
+        self.render_into(&mut buf);
+        buf.try_into_string()
+    }
 }
 
 impl IntoHtml for &str {
@@ -133,6 +347,11 @@ impl IntoHtml for &str {
     fn size_hint(&self) -> usize {
         self.len()
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        escaped_len(self)
+    }
 }
 
 impl IntoHtml for char {
@@ -150,8 +369,14 @@ impl IntoHtml for char {
     fn size_hint(&self) -> usize {
         self.len_utf8()
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        escaped_len(self.encode_utf8(&mut [0; 4]))
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl IntoHtml for String {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -167,8 +392,14 @@ impl IntoHtml for String {
     fn size_hint(&self) -> usize {
         self.len()
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        escaped_len(self)
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl IntoHtml for &String {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -179,6 +410,11 @@ impl IntoHtml for &String {
     fn size_hint(&self) -> usize {
         self.len()
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        escaped_len(self)
+    }
 }
 
 impl IntoHtml for bool {
@@ -193,7 +429,11 @@ impl IntoHtml for bool {
 
     #[inline]
     fn size_hint(&self) -> usize {
-        5
+        if *self {
+            4
+        } else {
+            5
+        }
     }
 }
 
@@ -218,6 +458,48 @@ impl<T: IntoHtml> IntoHtml for Option<T> {
             0
         }
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        if let Some(x) = self {
+            x.exact_size()
+        } else {
+            0
+        }
+    }
+}
+
+impl<T: IntoHtml, E: Into<Error>> IntoHtml for Result<T, E> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        match self {
+            Ok(x) => x.escape_and_write(buf),
+            Err(e) => buf.set_error(e),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        if let Ok(x) = self {
+            x.size_hint()
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        if let Ok(x) = self {
+            x.exact_size()
+        } else {
+            0
+        }
+    }
 }
 
 impl IntoHtml for () {
@@ -254,15 +536,24 @@ where
     #[inline]
     fn escape_and_write(self, buf: &mut Buffer) {
         let len = self.len();
+        let start = buf.len();
         for (i, x) in self.enumerate() {
-            if i == 0 {
-                buf.reserve(len * x.size_hint());
+            // Rendering was aborted, the remaining items would be discarded.
+            if buf.error().is_some() {
+                return;
             }
+            // Reserve for the remaining items, assuming they are as long as
+            // the ones written so far on average.
+            let average = (buf.len() - start).checked_div(i).unwrap_or(0);
+            buf.reserve(
+                (len - i).saturating_mul(cmp::max(average, x.size_hint())),
+            );
             x.escape_and_write(buf);
         }
     }
 }
 
+#[cfg(feature = "alloc")]
 impl<T: IntoHtml> IntoHtml for alloc::vec::Vec<T> {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -272,6 +563,9 @@ impl<T: IntoHtml> IntoHtml for alloc::vec::Vec<T> {
     #[inline]
     fn escape_and_write(self, buf: &mut Buffer) {
         for x in self {
+            if buf.error().is_some() {
+                return;
+            }
             x.escape_and_write(buf);
         }
     }
@@ -284,6 +578,15 @@ impl<T: IntoHtml> IntoHtml for alloc::vec::Vec<T> {
         }
         n
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        let mut n = 0;
+        for x in self {
+            n += x.exact_size();
+        }
+        n
+    }
 }
 
 impl<T: IntoHtml, const N: usize> IntoHtml for [T; N] {
@@ -295,6 +598,9 @@ impl<T: IntoHtml, const N: usize> IntoHtml for [T; N] {
     #[inline]
     fn escape_and_write(self, buf: &mut Buffer) {
         for x in self {
+            if buf.error().is_some() {
+                return;
+            }
             x.escape_and_write(buf);
         }
     }
@@ -307,8 +613,18 @@ impl<T: IntoHtml, const N: usize> IntoHtml for [T; N] {
         }
         n
     }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        let mut n = 0;
+        for x in self {
+            n += x.exact_size();
+        }
+        n
+    }
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> IntoHtml for alloc::borrow::Cow<'a, str> {
     #[inline]
     fn into_html(self) -> impl IntoHtml {
@@ -324,43 +640,48 @@ impl<'a> IntoHtml for alloc::borrow::Cow<'a, str> {
     fn size_hint(&self) -> usize {
         self.as_ref().len()
     }
-}
-impl IntoHtml for core::net::Ipv4Addr {
-    #[inline]
-    fn into_html(self) -> impl IntoHtml {
-        alloc::format!("{self}")
-    }
 
     #[inline]
-    fn size_hint(&self) -> usize {
-        15
+    fn exact_size(&self) -> usize {
+        escaped_len(self)
     }
 }
-
-impl IntoHtml for core::net::Ipv6Addr {
-    #[inline]
-    fn into_html(self) -> impl IntoHtml {
-        alloc::format!("{self}")
-    }
-
-    #[inline]
-    fn size_hint(&self) -> usize {
-        39
-    }
+macro_rules! via_display {
+    ($($ty:ty => $size_hint:expr),*) => {
+        $(
+            impl IntoHtml for $ty {
+                #[inline]
+                fn into_html(self) -> impl IntoHtml {
+                    self
+                }
+
+                #[inline]
+                fn escape_and_write(self, buf: &mut Buffer) {
+                    // The output contains no characters that need escaping.
+                    _ = write!(buf, "{self}");
+                }
+
+                #[inline]
+                fn size_hint(&self) -> usize {
+                    ($size_hint)(self)
+                }
+
+                fn exact_size(&self) -> usize {
+                    let mut counter = Counter(0);
+                    _ = write!(counter, "{self}");
+                    counter.0
+                }
+            }
+        )*
+    };
 }
 
-impl IntoHtml for core::net::IpAddr {
-    #[inline]
-    fn into_html(self) -> impl IntoHtml {
-        alloc::format!("{self}")
-    }
-
-    #[inline]
-    fn size_hint(&self) -> usize {
-        match self {
-            Self::V4(_) => 15,
-            Self::V6(_) => 39,
-        }
+via_display! {
+    core::net::Ipv4Addr => |_| 15,
+    core::net::Ipv6Addr => |_| 39,
+    core::net::IpAddr => |ip: &core::net::IpAddr| match ip {
+        core::net::IpAddr::V4(_) => 15,
+        core::net::IpAddr::V6(_) => 39,
     }
 }
 
@@ -386,6 +707,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_impl_result() {
+        use core::fmt;
+
+        let ok: Result<&str, fmt::Error> = Ok("<ok>");
+        assert_eq!(ok.try_into_string().unwrap(), "&lt;ok&gt;");
+
+        let err: Result<&str, fmt::Error> = Err(fmt::Error);
+        let html = ("before", err, "after");
+        assert!(html
+            .try_into_string()
+            .unwrap_err()
+            .downcast_ref::<fmt::Error>()
+            .is_some());
+    }
+
+    #[test]
+    fn test_exact_size() {
+        use alloc::vec;
+        use core::net::{IpAddr, Ipv6Addr};
+
+        fn check(html: impl IntoHtml + Clone) {
+            let len = html.clone().into_string().len();
+            assert_eq!(html.exact_size(), len);
+            let exact = html.into_string_exact();
+            assert_eq!((exact.len(), exact.capacity()), (len, len));
+        }
+
+        check(("a < b", '&', String::from("\"x\""), true, false));
+        check((0u8, -7i32, i64::MIN, u128::MAX, 1.5f64, -0.1f32));
+        check(vec![Some("<"), None, Some(">")]);
+        check([IpAddr::V6(Ipv6Addr::LOCALHOST), "::2".parse().unwrap()]);
+
+        assert_eq!(1234u16.size_hint(), 4);
+        assert_eq!((-1i8).size_hint(), 2);
+        assert_eq!(0usize.size_hint(), 1);
+    }
+
     #[test]
     fn test_impl_ipv4addr() {
         use core::net::Ipv4Addr;
@@ -0,0 +1,217 @@
+//! Limits on the size of rendered output.
+//!
+//! A template that renders an unbounded iterator, or recurses without end,
+//! would otherwise grow its buffer until the process runs out of memory.
+//! [`Limits`] cap the length of the output and the nesting depth of
+//! [`nested`] values, and turn a runaway render into an [`Error`]:
+//!
+//! ```
+//! # use vy::{limit::*, prelude::*};
+//! let limits = Limits::new().max_len(64);
+//!
+//! assert_eq!(limits.try_render(p!("Hello")).unwrap(), "<p>Hello</p>");
+//!
+//! let error = limits.try_render(ul!((0..1000).map(|i| li!(i))));
+//! assert_eq!(
+//!     error.unwrap_err().downcast_ref(),
+//!     Some(&LimitExceeded::Length(64))
+//! );
+//! ```
+//!
+//! The same limits can be set on a [`Buffer`] directly with
+//! [`Buffer::set_max_len`] and [`Buffer::set_max_depth`].
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::{error::Error as StdError, fmt};
+
+#[cfg(feature = "alloc")]
+use crate::Error;
+use crate::{Buffer, IntoHtml};
+
+/// The error recorded when a render exceeds a limit of its [`Buffer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// The output exceeded the given maximum length in bytes.
+    Length(usize),
+    /// The nesting exceeded the given maximum depth.
+    Depth(usize),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Length(max) => {
+                write!(f, "output exceeds the maximum length of {max} bytes")
+            }
+            Self::Depth(max) => {
+                write!(f, "nesting exceeds the maximum depth of {max}")
+            }
+        }
+    }
+}
+
+impl StdError for LimitExceeded {}
+
+/// Limits for rendering.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    max_len: Option<usize>,
+    max_depth: Option<usize>,
+}
+
+impl Limits {
+    /// Creates limits that allow any output.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            max_len: None,
+            max_depth: None,
+        }
+    }
+
+    /// Sets the maximum length of the output in bytes.
+    #[inline]
+    pub const fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Sets the maximum nesting depth of [`nested`] values.
+    #[inline]
+    pub const fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Sets these limits on `buf`.
+    #[inline]
+    pub fn apply(&self, buf: &mut Buffer) {
+        buf.set_max_len(self.max_len);
+        buf.set_max_depth(self.max_depth);
+    }
+
+    /// Allocates a new [`String`] containing the HTML, or returns the first
+    /// error encountered while rendering, including exceeded limits.
+    #[cfg(feature = "alloc")]
+    pub fn try_render(&self, html: impl IntoHtml) -> Result<String, Error> {
+        let mut buf = Buffer::new();
+        self.apply(&mut buf);
+        html.render_into(&mut buf);
+        buf.try_into_string()
+    }
+}
+
+/// Renders `html` one level deeper, counting towards the maximum depth of
+/// the buffer.
+///
+/// Wrap the recursive calls of components that render trees, such as nested
+/// comments, so that unexpectedly deep input is rejected:
+///
+/// ```
+/// # use vy::{limit::*, prelude::*};
+/// struct Comment {
+///     text: &'static str,
+///     replies: Vec<Comment>,
+/// }
+///
+/// impl IntoHtml for Comment {
+///     fn into_html(self) -> impl IntoHtml {
+///         li!(self.text, ul!(nested(self.replies)))
+///     }
+/// }
+///
+/// let thread = Comment {
+///     text: "a",
+///     replies: vec![Comment {
+///         text: "b",
+///         replies: vec![],
+///     }],
+/// };
+/// assert!(Limits::new().max_depth(0).try_render(thread).is_err());
+/// ```
+#[inline]
+pub fn nested<H: IntoHtml>(html: H) -> Nested<H> {
+    Nested(html)
+}
+
+/// An [`IntoHtml`] value created by [`nested`].
+pub struct Nested<H>(H);
+
+impl<H: IntoHtml> IntoHtml for Nested<H> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        if buf.enter() {
+            self.0.escape_and_write(buf);
+            buf.exit();
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.size_hint()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.0.exact_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn max_len() {
+        let mut buf = Buffer::new();
+        buf.set_max_len(Some(8));
+        buf.push_str("abcd");
+        buf.push('é');
+        buf.push_str("ab");
+        assert!(buf.error().is_none());
+        1u8.escape_and_write(&mut buf);
+        buf.push('x');
+        assert_eq!(buf.as_str(), "abcdéab");
+        assert_eq!(
+            buf.error().unwrap().downcast_ref(),
+            Some(&LimitExceeded::Length(8))
+        );
+        assert!(buf.capacity() <= 8);
+    }
+
+    #[test]
+    fn fits_exactly() {
+        let limits = Limits::new().max_len(6);
+        assert_eq!(limits.try_render(("a<", 1u64)).unwrap(), "a&lt;1");
+        assert!(limits.try_render(("a<", 10u64)).is_err());
+        assert_eq!(limits.try_render('\u{1F980}').unwrap(), "\u{1F980}");
+    }
+
+    #[test]
+    fn reserve_is_capped() {
+        let mut buf = Buffer::new();
+        buf.set_max_len(Some(16));
+        buf.reserve(1 << 30);
+        assert_eq!(buf.capacity(), 16);
+        assert!(buf.error().is_none());
+    }
+
+    #[test]
+    fn max_depth() {
+        let limits = Limits::new().max_depth(2);
+        assert_eq!(
+            limits.try_render(nested(("a", nested("b")))).unwrap(),
+            "ab"
+        );
+        let error = limits.try_render(nested(nested(nested("c")))).unwrap_err();
+        assert_eq!(error.to_string(), "nesting exceeds the maximum depth of 2");
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    pretty::{write_marked, Mark},
+    Buffer, IntoHtml,
+};
+
+/// A type-erased template made of a static string table and dynamic slots.
+///
+/// This is what the `erased!` macro expands to. Instead of nesting every part
+/// of a template into a tuple, the static text is stored as a `&'static
+/// [&'static str]` and each dynamic expression is boxed behind a trait object.
+/// This keeps the generated types small, which reduces compile times for large
+/// templates at the cost of one allocation per template and a dynamic call per
+/// slot.
+///
+/// When rendered, the static parts and slots are interleaved, starting and
+/// ending with a static part: `statics[0] slots[0] statics[1] .. statics[n]`.
+pub struct Markup<'a> {
+    statics: &'static [&'static str],
+    marks: &'static [&'static [Mark]],
+    slots: Vec<Box<dyn Slot + 'a>>,
+}
+
+impl<'a> Markup<'a> {
+    /// Creates a new [`Markup`] from a table of static parts and the dynamic
+    /// slots to place between them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `statics` does not contain exactly one more part than
+    /// `slots`.
+    #[inline]
+    pub fn new(
+        statics: &'static [&'static str],
+        slots: Vec<Box<dyn Slot + 'a>>,
+    ) -> Self {
+        assert_eq!(
+            statics.len(),
+            slots.len() + 1,
+            "static parts must surround every slot"
+        );
+        Self {
+            statics,
+            marks: &[],
+            slots,
+        }
+    }
+
+    /// Creates a new [`Markup`] whose static parts have structural marks for
+    /// [`pretty`](crate::pretty) output, written by the `erased!` macro.
+    #[doc(hidden)]
+    #[inline]
+    pub fn marked(
+        statics: &'static [&'static str],
+        marks: &'static [&'static [Mark]],
+        slots: Vec<Box<dyn Slot + 'a>>,
+    ) -> Self {
+        assert_eq!(statics.len(), marks.len());
+        Self {
+            marks,
+            ..Self::new(statics, slots)
+        }
+    }
+
+    /// Erases the type of `value` so that it can be used as a slot.
+    #[inline]
+    pub fn slot<T: IntoHtml + 'a>(value: T) -> Box<dyn Slot + 'a> {
+        Box::new(value)
+    }
+}
+
+impl IntoHtml for Markup<'_> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let marks = |i| self.marks.get(i).copied().unwrap_or_default();
+        write_marked(buf, self.statics[0], marks(0));
+        for (i, slot) in self.slots.into_iter().enumerate() {
+            if buf.error().is_some() {
+                return;
+            }
+            slot.escape_and_write_dyn(buf);
+            write_marked(buf, self.statics[i + 1], marks(i + 1));
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        let mut n = 0;
+        for part in self.statics {
+            n += part.len();
+        }
+        for slot in &self.slots {
+            n += slot.size_hint_dyn();
+        }
+        n
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        let mut n = 0;
+        for part in self.statics {
+            n += part.len();
+        }
+        for slot in &self.slots {
+            n += slot.exact_size_dyn();
+        }
+        n
+    }
+}
+
+/// An object-safe version of [`IntoHtml`], used for the slots of a
+/// [`Markup`].
+pub trait Slot {
+    /// Writes the HTML into the provided [`Buffer`].
+    fn escape_and_write_dyn(self: Box<Self>, buf: &mut Buffer);
+
+    /// See [`IntoHtml::size_hint`].
+    fn size_hint_dyn(&self) -> usize;
+
+    /// See [`IntoHtml::exact_size`].
+    fn exact_size_dyn(&self) -> usize;
+}
+
+impl<T: IntoHtml> Slot for T {
+    #[inline]
+    fn escape_and_write_dyn(self: Box<Self>, buf: &mut Buffer) {
+        (*self).escape_and_write(buf);
+    }
+
+    #[inline]
+    fn size_hint_dyn(&self) -> usize {
+        self.size_hint()
+    }
+
+    #[inline]
+    fn exact_size_dyn(&self) -> usize {
+        self.exact_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use super::*;
+    use crate::escape::PreEscaped;
+
+    #[test]
+    fn interleaves_parts_and_slots() {
+        let name = String::from("<world>");
+        let markup = Markup::new(
+            &["<p>hello ", "", "</p>"],
+            vec![Markup::slot(&name), Markup::slot(PreEscaped("!"))],
+        );
+        assert_eq!(markup.size_hint(), 21);
+        assert_eq!(markup.into_string(), "<p>hello &lt;world&gt;!</p>");
+    }
+
+    #[test]
+    fn static_only() {
+        let markup = Markup::new(&["<br>"], vec![]);
+        assert_eq!(markup.into_string(), "<br>");
+    }
+
+    #[test]
+    #[should_panic]
+    fn mismatched_parts() {
+        Markup::new(&["<p>", "</p>"], vec![]);
+    }
+}
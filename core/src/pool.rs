@@ -0,0 +1,429 @@
+//! Reusing buffers between renders.
+//!
+//! Servers render many pages of similar sizes, and every
+//! [`IntoHtml::into_string`] call allocates a new buffer for them. A
+//! [`BufferPool`] keeps the buffers of finished renders around, so that the
+//! next render can reuse their allocation:
+//!
+//! ```
+//! # use vy::{pool::BufferPool, prelude::*};
+//! static POOL: BufferPool = BufferPool::new();
+//!
+//! fn respond(name: &str) -> Vec<u8> {
+//!     // Copy the output into the response body, then return the buffer to
+//!     // the pool.
+//!     POOL.render(h1!("Hello, ", name))
+//!         .map(|html| html.as_bytes().to_vec())
+//! }
+//!
+//! assert_eq!(respond("Ann"), b"<h1>Hello, Ann</h1>");
+//! assert_eq!(POOL.idle(), 1);
+//! ```
+//!
+//! The output is copied once into the response body, since the allocation
+//! goes back to the pool. With the `bytes` feature,
+//! [`PooledBuffer::into_bytes`] copies it into [`Bytes`](bytes::Bytes).
+//!
+//! [`LocalBufferPool`] is the same without synchronization, keeping one pool
+//! per thread.
+//!
+//! Buffers that grew larger than the pool's
+//! [`max_capacity`](BufferPool::max_capacity) are freed instead of being
+//! returned, so that a single huge page does not hold on to its memory
+//! forever. When taking a buffer, the pool picks the smallest one that fits
+//! the [`size_hint`](IntoHtml::size_hint) of the template.
+
+use alloc::vec::Vec;
+use core::{
+    cell::{Cell, RefCell},
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{Buffer, Error, IntoHtml};
+
+const DEFAULT_MAX_BUFFERS: usize = 16;
+const DEFAULT_MAX_CAPACITY: usize = 1 << 20;
+
+/// A pool of buffers that can be shared between threads.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Buffer>>,
+    max_buffers: usize,
+    max_capacity: usize,
+}
+
+impl BufferPool {
+    /// Creates an empty pool, which keeps up to 16 buffers of up to 1 MiB.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers: DEFAULT_MAX_BUFFERS,
+            max_capacity: DEFAULT_MAX_CAPACITY,
+        }
+    }
+
+    /// Sets the maximum number of idle buffers kept in the pool.
+    #[inline]
+    pub const fn max_buffers(mut self, max_buffers: usize) -> Self {
+        self.max_buffers = max_buffers;
+        self
+    }
+
+    /// Sets the capacity in bytes above which buffers are freed instead of
+    /// being returned to the pool.
+    #[inline]
+    pub const fn max_capacity(mut self, max_capacity: usize) -> Self {
+        self.max_capacity = max_capacity;
+        self
+    }
+
+    /// Takes an empty buffer from the pool, or allocates a new one.
+    #[inline]
+    pub fn get(&self) -> PooledBuffer<'_> {
+        self.get_with_capacity(0)
+    }
+
+    /// Takes an empty buffer with a capacity of at least `capacity` bytes
+    /// from the pool, or allocates a new one.
+    pub fn get_with_capacity(&self, capacity: usize) -> PooledBuffer<'_> {
+        let buf = take(&mut self.lock(), capacity);
+        PooledBuffer::new(buf, Owner::Shared(self))
+    }
+
+    /// Renders `html` into a buffer from the pool.
+    pub fn render(&self, html: impl IntoHtml) -> PooledBuffer<'_> {
+        let html = html.into_html();
+        let size = html.size_hint();
+        let mut buf = self.get_with_capacity(size + (size / 10));
+        html.escape_and_write(&mut buf);
+        buf
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    #[inline]
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Frees all idle buffers.
+    #[inline]
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn put(&self, buf: Buffer) {
+        if buf.capacity() <= self.max_capacity {
+            put(&mut self.lock(), buf, self.max_buffers);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Buffer>> {
+        // The buffers are valid even if a thread panicked while holding the
+        // lock.
+        self.buffers.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Default for BufferPool {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+std::thread_local! {
+    static LOCAL: RefCell<Vec<Buffer>> = const { RefCell::new(Vec::new()) };
+    static LOCAL_LIMITS: Cell<(usize, usize)> =
+        const { Cell::new((DEFAULT_MAX_BUFFERS, DEFAULT_MAX_CAPACITY)) };
+}
+
+/// A pool of buffers for the current thread.
+///
+/// Buffers are returned to the pool of the thread that drops them.
+pub struct LocalBufferPool;
+
+impl LocalBufferPool {
+    /// Sets the maximum number of idle buffers kept in the pool of the
+    /// current thread, and the capacity in bytes above which buffers are
+    /// freed instead, which default to 16 buffers and 1 MiB.
+    pub fn set_limits(max_buffers: usize, max_capacity: usize) {
+        LOCAL_LIMITS.with(|limits| limits.set((max_buffers, max_capacity)));
+        LOCAL.with_borrow_mut(|buffers| {
+            buffers.retain(|buf| buf.capacity() <= max_capacity);
+            // Keep the largest buffers.
+            let excess = buffers.len().saturating_sub(max_buffers);
+            buffers.drain(..excess);
+        });
+    }
+
+    /// Takes an empty buffer from the pool, or allocates a new one.
+    #[inline]
+    pub fn get() -> PooledBuffer<'static> {
+        Self::get_with_capacity(0)
+    }
+
+    /// Takes an empty buffer with a capacity of at least `capacity` bytes
+    /// from the pool, or allocates a new one.
+    pub fn get_with_capacity(capacity: usize) -> PooledBuffer<'static> {
+        let buf = LOCAL.with_borrow_mut(|buffers| take(buffers, capacity));
+        PooledBuffer::new(buf, Owner::Local)
+    }
+
+    /// Renders `html` into a buffer from the pool.
+    pub fn render(html: impl IntoHtml) -> PooledBuffer<'static> {
+        let html = html.into_html();
+        let size = html.size_hint();
+        let mut buf = Self::get_with_capacity(size + (size / 10));
+        html.escape_and_write(&mut buf);
+        buf
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    #[inline]
+    pub fn idle() -> usize {
+        LOCAL.with_borrow(Vec::len)
+    }
+
+    /// Frees all idle buffers.
+    #[inline]
+    pub fn clear() {
+        LOCAL.with_borrow_mut(Vec::clear);
+    }
+
+    fn put(buf: Buffer) {
+        // The pool is gone if the thread is exiting.
+        let Ok((max_buffers, max_capacity)) = LOCAL_LIMITS.try_with(Cell::get)
+        else {
+            return;
+        };
+        if buf.capacity() <= max_capacity {
+            _ = LOCAL.try_with(|buffers| {
+                put(&mut buffers.borrow_mut(), buf, max_buffers)
+            });
+        }
+    }
+}
+
+/// Takes the smallest buffer that fits `capacity`, or the largest one.
+///
+/// `buffers` is sorted by capacity.
+fn take(buffers: &mut Vec<Buffer>, capacity: usize) -> Buffer {
+    let mut buf = match buffers.iter().position(|b| b.capacity() >= capacity) {
+        Some(i) => buffers.remove(i),
+        None => buffers.pop().unwrap_or_default(),
+    };
+    buf.reserve(capacity);
+    buf
+}
+
+/// Returns `buf` to `buffers`, replacing the smallest buffer if full.
+fn put(buffers: &mut Vec<Buffer>, mut buf: Buffer, max_buffers: usize) {
+    buf.reset();
+    if buffers.len() < max_buffers {
+        buffers.push(buf);
+        // Keep the buffers sorted by capacity, so that `take` without a
+        // fitting buffer pops the largest one.
+        buffers.sort_unstable_by_key(Buffer::capacity);
+    } else if let Some(smallest) = buffers.first_mut() {
+        if smallest.capacity() < buf.capacity() {
+            *smallest = buf;
+            buffers.sort_unstable_by_key(Buffer::capacity);
+        }
+    }
+}
+
+enum Owner<'a> {
+    Shared(&'a BufferPool),
+    Local,
+}
+
+/// A buffer that returns to its pool when dropped.
+///
+/// Dereferences to the [`Buffer`] holding the rendered HTML.
+pub struct PooledBuffer<'a> {
+    buf: ManuallyDrop<Buffer>,
+    owner: Owner<'a>,
+}
+
+impl<'a> PooledBuffer<'a> {
+    #[inline]
+    fn new(buf: Buffer, owner: Owner<'a>) -> Self {
+        Self {
+            buf: ManuallyDrop::new(buf),
+            owner,
+        }
+    }
+
+    /// Passes the rendered HTML to `f`, for example to copy it into a
+    /// response body, and returns the buffer to the pool afterwards.
+    #[inline]
+    pub fn map<T>(self, f: impl FnOnce(&str) -> T) -> T {
+        f(self.as_str())
+    }
+
+    /// Like [`map`](Self::map), but returns the error that aborted rendering
+    /// instead, if any.
+    #[inline]
+    pub fn try_map<T>(mut self, f: impl FnOnce(&str) -> T) -> Result<T, Error> {
+        match self.buf.take_error() {
+            Some(error) => Err(error),
+            None => Ok(f(self.as_str())),
+        }
+    }
+
+    /// Copies the rendered HTML into [`Bytes`](bytes::Bytes), for example
+    /// to use it as a response body, and returns the buffer to the pool.
+    ///
+    /// Any error recorded while rendering is ignored, use
+    /// [`try_map`](Self::try_map) with [`Bytes::copy_from_slice`] to handle
+    /// it.
+    ///
+    /// [`Bytes::copy_from_slice`]: bytes::Bytes::copy_from_slice
+    #[cfg(feature = "bytes")]
+    #[inline]
+    pub fn into_bytes(self) -> bytes::Bytes {
+        bytes::Bytes::copy_from_slice(self.as_bytes())
+    }
+
+    /// Takes the buffer out of the pool.
+    #[inline]
+    pub fn into_inner(self) -> Buffer {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again.
+        unsafe { ManuallyDrop::take(&mut this.buf) }
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Buffer;
+
+    #[inline]
+    fn deref(&self) -> &Buffer {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Buffer {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `self.buf` is not used after this.
+        let buf = unsafe { ManuallyDrop::take(&mut self.buf) };
+        match self.owner {
+            Owner::Shared(pool) => pool.put(buf),
+            Owner::Local => LocalBufferPool::put(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let pool = BufferPool::new();
+        let ptr = {
+            let buf = pool.render(("<a>", 1));
+            assert_eq!(buf.as_str(), "&lt;a&gt;1");
+            buf.as_mut_ptr()
+        };
+        assert_eq!(pool.idle(), 1);
+
+        let buf = pool.get();
+        assert!(buf.is_empty());
+        assert_eq!(buf.as_mut_ptr(), ptr);
+        assert_eq!(pool.idle(), 0);
+
+        let buf = buf.into_inner();
+        assert_eq!(pool.idle(), 0);
+        drop(buf);
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[test]
+    fn reset() {
+        let pool = BufferPool::new();
+        {
+            let mut buf = pool.get();
+            buf.set_error(Error::msg("failed"));
+            buf.provide(1u8);
+            buf.set_escape_policy(crate::escape::EscapePolicy::Strict);
+        }
+        let buf = pool.get();
+        assert!(buf.error().is_none());
+        assert!(buf.use_context::<u8>().is_none());
+        assert_eq!(buf.escape_policy(), crate::escape::EscapePolicy::Minimal);
+        drop(buf);
+
+        {
+            let mut buf = pool.get();
+            buf.set_max_len(Some(5));
+            buf.set_max_depth(Some(0));
+        }
+        let buf = pool.get();
+        assert_eq!(buf.max_len(), None);
+        assert_eq!(buf.max_depth(), None);
+        drop(buf);
+        let html = pool.render(crate::limit::nested("hello world"));
+        assert!(html.error().is_none());
+        assert_eq!(html.as_str(), "hello world");
+    }
+
+    #[test]
+    fn limits() {
+        let pool = BufferPool::new().max_buffers(2).max_capacity(64);
+        let bufs = [
+            pool.get_with_capacity(8),
+            pool.get_with_capacity(16),
+            pool.get_with_capacity(32),
+            pool.get_with_capacity(128),
+        ];
+        drop(bufs);
+        assert_eq!(pool.idle(), 2);
+
+        // The smallest buffer that fits is taken.
+        assert_eq!(pool.get_with_capacity(20).capacity(), 32);
+        assert_eq!(pool.get_with_capacity(10).capacity(), 16);
+        assert!(pool.get_with_capacity(40).capacity() >= 40);
+    }
+
+    #[test]
+    fn map() {
+        let pool = BufferPool::new();
+        assert_eq!(pool.render("a").map(str::len), 1);
+        let failed: Result<&str, Error> = Err(Error::msg("failed"));
+        assert!(pool.render(failed).try_map(str::len).is_err());
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn into_bytes() {
+        let pool = BufferPool::new();
+        let bytes = pool.render(("<a>", 1)).into_bytes();
+        assert_eq!(bytes, "&lt;a&gt;1");
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn local() {
+        LocalBufferPool::clear();
+        let html = LocalBufferPool::render("a");
+        assert_eq!(html.as_str(), "a");
+        drop(html);
+        assert_eq!(LocalBufferPool::idle(), 1);
+
+        LocalBufferPool::set_limits(0, 0);
+        assert_eq!(LocalBufferPool::idle(), 0);
+        drop(LocalBufferPool::get());
+        assert_eq!(LocalBufferPool::idle(), 0);
+    }
+}
@@ -0,0 +1,327 @@
+//! Pretty-printed output.
+//!
+//! [`IntoHtml::into_pretty_string`](crate::IntoHtml::into_pretty_string)
+//! renders HTML with one block element per line, indented by nesting depth.
+//! Text and phrasing content such as `a`, `b` or `span` are kept on the line
+//! of their parent, and the content of `pre`, `textarea`, `script` and
+//! `style` elements is written verbatim, so the output renders the same as
+//! the minified HTML.
+//!
+//! ```
+//! # use vy::prelude::*;
+//! let page = html!(body!(
+//!     h1!("Hello ", b!("world")),
+//!     ul!(li!("a"), li!("b")),
+//!     pre!("  x\n  y")
+//! ));
+//!
+//! assert_eq!(
+//!     page.into_pretty_string(),
+//!     "<html>
+//!   <body>
+//!     <h1>Hello <b>world</b></h1>
+//!     <ul>
+//!       <li>a</li>
+//!       <li>b</li>
+//!     </ul>
+//!     <pre>  x
+//!   y</pre>
+//!   </body>
+//! </html>"
+//! );
+//! ```
+//!
+//! The element macros record where block and verbatim elements start and end
+//! in their static HTML, and the output is indented while it is written, so
+//! pretty-printing costs a single render. HTML written by other means, such
+//! as [`PreEscaped`](crate::PreEscaped) strings, is kept as is.
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+#[cfg(feature = "alloc")]
+use crate::buffer::Filter;
+use crate::{Buffer, IntoHtml};
+
+/// The indentation of each nesting level.
+#[cfg(feature = "alloc")]
+const INDENT: &str = "  ";
+
+/// A structural mark in static HTML, at a byte offset.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mark {
+    /// The start tag of a block element.
+    Open(usize),
+    /// The end tag of a block element.
+    Close(usize),
+    /// A void block element.
+    Void(usize),
+    /// The start of the content of a verbatim element.
+    Verbatim(usize),
+    /// The end of the content of a verbatim element.
+    EndVerbatim(usize),
+}
+
+impl Mark {
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn offset(self) -> usize {
+        match self {
+            Self::Open(i)
+            | Self::Close(i)
+            | Self::Void(i)
+            | Self::Verbatim(i)
+            | Self::EndVerbatim(i) => i,
+        }
+    }
+}
+
+/// Static HTML with its structural marks, written by the element macros.
+#[doc(hidden)]
+pub struct Marked(pub &'static str, pub &'static [Mark]);
+
+impl IntoHtml for Marked {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        write_marked(buf, self.0, self.1);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Writes static HTML, which is pretty-printed following `marks` if the
+/// buffer pretty-prints its output.
+#[inline]
+pub(crate) fn write_marked(buf: &mut Buffer, html: &str, marks: &[Mark]) {
+    #[cfg(feature = "alloc")]
+    if buf.is_filtered() {
+        buf.push_marked(html, marks);
+        return;
+    }
+    let _ = marks;
+    buf.push_str(html);
+}
+
+/// Renders `html` pretty-printed.
+#[cfg(feature = "alloc")]
+pub(crate) fn render(html: impl IntoHtml) -> String {
+    let mut buf = Buffer::new();
+    buf.set_filter(Some(Filter::Pretty(Box::default())));
+    html.render_into(&mut buf);
+    buf.set_filter(None);
+    buf.into_string()
+}
+
+/// Indents HTML as it is written into a buffer.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub(crate) struct PrettyWriter {
+    /// Whether each open block element has block children.
+    blocks: Vec<bool>,
+    verbatim: usize,
+    /// Whether the end tag of a block element, or a void block element, is
+    /// being written.
+    in_tag: bool,
+    /// Whether the last thing written was a block element.
+    after_block: bool,
+    /// Whether a line was started and only indented.
+    line_start: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl PrettyWriter {
+    /// Writes `html`, with the structural `marks` at its offsets.
+    pub(crate) fn write(
+        &mut self,
+        buf: &mut Buffer,
+        html: &str,
+        marks: &[Mark],
+    ) {
+        let mut last = 0;
+        for &mark in marks {
+            let at = mark.offset();
+            self.content(buf, &html[last..at]);
+            last = at;
+            self.mark(buf, mark);
+        }
+        self.content(buf, &html[last..]);
+    }
+
+    fn mark(&mut self, buf: &mut Buffer, mark: Mark) {
+        match mark {
+            Mark::Verbatim(_) => self.verbatim += 1,
+            Mark::EndVerbatim(_) => {
+                self.verbatim = self.verbatim.saturating_sub(1);
+            }
+            _ if self.verbatim > 0 => {}
+            Mark::Open(_) | Mark::Void(_) => {
+                if let Some(parent) = self.blocks.last_mut() {
+                    *parent = true;
+                }
+                self.new_line(buf);
+                if let Mark::Open(_) = mark {
+                    self.blocks.push(false);
+                } else {
+                    self.in_tag = true;
+                }
+            }
+            Mark::Close(_) => {
+                if self.blocks.pop() == Some(true) {
+                    self.new_line(buf);
+                }
+                self.in_tag = true;
+            }
+        }
+    }
+
+    /// Writes content, starting a new line if it follows a block element.
+    fn content(&mut self, buf: &mut Buffer, mut html: &str) {
+        if self.in_tag && self.verbatim == 0 {
+            let Some(end) = html.find('>') else {
+                buf.push_str(html);
+                return;
+            };
+            buf.push_str(&html[..=end]);
+            html = &html[end + 1..];
+            self.in_tag = false;
+            self.after_block = true;
+            self.line_start = false;
+        }
+        if html.is_empty() {
+            return;
+        }
+        if self.verbatim == 0 {
+            if self.after_block || self.line_start {
+                html = html.trim_start_matches(is_whitespace);
+                if html.is_empty() {
+                    return;
+                }
+            }
+            if self.after_block {
+                self.new_line(buf);
+            }
+            self.after_block = false;
+            self.line_start = false;
+        }
+        buf.push_str(html);
+    }
+
+    /// Starts an indented line, removing the trailing whitespace of the
+    /// current one.
+    fn new_line(&mut self, buf: &mut Buffer) {
+        let end = buf.as_str().trim_end_matches(is_whitespace).len();
+        buf.truncate(end);
+        if !buf.is_empty() {
+            buf.push('\n');
+            for _ in 0..self.blocks.len() {
+                buf.push_str(INDENT);
+            }
+        }
+        self.after_block = false;
+        self.line_start = true;
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[inline]
+fn is_whitespace(ch: char) -> bool {
+    matches!(ch, '\t' | '\n' | '\x0c' | '\r' | ' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::PreEscaped;
+
+    fn pretty(parts: &[(&'static str, &'static [Mark])]) -> String {
+        render(
+            parts
+                .iter()
+                .map(|&(html, marks)| Marked(html, marks))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn blocks_and_inline() {
+        assert_eq!(
+            pretty(&[
+                (
+                    "<!DOCTYPE html><div><p>a ",
+                    &[Mark::Open(15), Mark::Open(20)]
+                ),
+                (
+                    "<b>b</b></p><!-- c --><hr><p></p></div>",
+                    &[
+                        Mark::Close(8),
+                        Mark::Void(22),
+                        Mark::Open(26),
+                        Mark::Close(29),
+                        Mark::Close(33)
+                    ]
+                ),
+            ]),
+            "<!DOCTYPE html>\n<div>\n  <p>a <b>b</b></p>\n  <!-- c -->\n  \
+             <hr>\n  <p></p>\n</div>"
+        );
+    }
+
+    #[test]
+    fn mixed_content() {
+        assert_eq!(
+            pretty(&[(
+                "<div> text <span>x</span><div>y</div>more </div>",
+                &[
+                    Mark::Open(0),
+                    Mark::Open(25),
+                    Mark::Close(31),
+                    Mark::Close(42)
+                ]
+            )]),
+            "<div> text <span>x</span>\n  <div>y</div>\n  more\n</div>"
+        );
+    }
+
+    #[test]
+    fn verbatim() {
+        assert_eq!(
+            pretty(&[(
+                "<div><p>a</p><textarea> <p>\n</textarea><script>if (a<b) \
+                 {}</script></div>",
+                &[
+                    Mark::Open(0),
+                    Mark::Open(5),
+                    Mark::Close(9),
+                    Mark::Verbatim(23),
+                    Mark::EndVerbatim(28),
+                    Mark::Open(39),
+                    Mark::Verbatim(47),
+                    Mark::EndVerbatim(58),
+                    Mark::Close(58),
+                    Mark::Close(67)
+                ]
+            )]),
+            "<div>\n  <p>a</p>\n  <textarea> <p>\n</textarea>\n  <script>if \
+             (a<b) {}</script>\n</div>"
+        );
+    }
+
+    #[test]
+    fn unmarked() {
+        let html = (
+            Marked("<div>", &[Mark::Open(0)]),
+            PreEscaped("<p>a</p>"),
+            Marked("</div>", &[Mark::Close(0)]),
+        );
+        assert_eq!(render(html), "<div><p>a</p></div>");
+    }
+}
@@ -0,0 +1,105 @@
+//! Typed routes.
+//!
+//! A [`Route`] is usually an enum with one variant per page of an
+//! application, derived with `#[derive(Route)]`. Each variant is given a
+//! path template, whose `{placeholders}` name the fields of the variant (or
+//! their index, for tuple variants). Every placeholder must span a whole
+//! path segment.
+//!
+//! The same definition renders links and parses request paths, so links
+//! cannot get out of sync with the routes they point to:
+//!
+//! ```
+//! # use vy::prelude::*;
+//! #[derive(Debug, PartialEq, Route)]
+//! enum AppRoute {
+//!     #[route("/")]
+//!     Home,
+//!     #[route("/users/{id}")]
+//!     User { id: u32 },
+//!     #[route("/tags/{0}/posts")]
+//!     Tag(String),
+//! }
+//!
+//! assert_eq!(
+//!     a!(href = AppRoute::Tag("a/b".into()), "Tag").into_string(),
+//!     "<a href=\"/tags/a%2Fb/posts\">Tag</a>"
+//! );
+//! assert_eq!(AppRoute::User { id: 7 }.to_string(), "/users/7");
+//!
+//! assert_eq!(AppRoute::from_path("/"), Some(AppRoute::Home));
+//! assert_eq!(
+//!     AppRoute::from_path("/users/7?tab=posts"),
+//!     Some(AppRoute::User { id: 7 })
+//! );
+//! assert_eq!(AppRoute::from_path("/users/me"), None);
+//! ```
+//!
+//! Deriving [`Route`] also implements [`IntoHtml`](crate::IntoHtml) and
+//! [`Display`](core::fmt::Display) for the type and its references.
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::str::{FromStr, Split};
+
+use crate::url::{percent_decode, Url, UrlWriter};
+
+/// A type that maps to and from URL paths.
+pub trait Route: Sized {
+    /// Writes the path of this route, percent-encoding its parameters.
+    fn write_path(&self, w: &mut UrlWriter);
+
+    /// Parses a path into a route, returning `None` if it matches no route.
+    ///
+    /// Any query string or fragment is ignored. Without the `alloc` feature,
+    /// parameters that contain percent-encoded characters do not match.
+    fn from_path(path: &str) -> Option<Self>;
+
+    /// Returns the path of this route as a [`Url`].
+    #[inline]
+    fn url(&self) -> Url<impl Fn(&mut UrlWriter) + '_> {
+        Url::new(move |w: &mut UrlWriter| self.write_path(w))
+    }
+
+    /// Allocates a new [`String`] containing the path of this route.
+    #[cfg(feature = "alloc")]
+    fn to_path(&self) -> String {
+        use core::fmt::Write;
+
+        let mut path = String::new();
+        _ = write!(path, "{}", self.url());
+        path
+    }
+}
+
+/// Returns an iterator over the segments of `path`, without its query string
+/// and fragment.
+#[doc(hidden)]
+pub fn segments(path: &str) -> Split<'_, char> {
+    let end = path.find(['?', '#']).unwrap_or(path.len());
+    path[..end].split('/')
+}
+
+/// Percent-decodes and parses a path segment.
+#[doc(hidden)]
+pub fn parse_segment<T: FromStr>(segment: &str) -> Option<T> {
+    percent_decode(segment)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_path() {
+        assert!(segments("/a/b?c=/d#/e").eq(["", "a", "b"]));
+        assert!(segments("/").eq(["", ""]));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_segment::<u32>("42"), Some(42));
+        assert_eq!(parse_segment::<u32>("x"), None);
+        assert_eq!(parse_segment::<String>("a%2Fb").as_deref(), Some("a/b"));
+    }
+}
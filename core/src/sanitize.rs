@@ -0,0 +1,487 @@
+//! Sanitization of untrusted HTML.
+//!
+//! User supplied HTML, such as rich-text comments, must never be rendered
+//! with [`PreEscaped`](crate::escape::PreEscaped). Instead, sanitize it with a
+//! [`Policy`] that lists the elements, attributes and URL schemes to keep.
+//! Everything else is removed, and the result is re-serialized as
+//! well-formed HTML.
+//!
+//! ```
+//! # use vy::{prelude::*, sanitize::Policy};
+//! let policy = Policy::basic();
+//! let comment = policy.sanitize(
+//!     "<p onclick=\"steal()\">Hi <a \
+//!      href=\"javascript:steal()\">there</a><script>steal()</script>",
+//! );
+//!
+//! assert_eq!(
+//!     div!(class = "comment", comment).into_string(),
+//!     "<div class=\"comment\"><p>Hi <a rel=\"nofollow noopener \
+//!      noreferrer\">there</a></p></div>"
+//! );
+//! ```
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use crate::{
+    escape::{escape_into, unescape, unescape_attr},
+    tokenizer::{Token, Tokenizer, VOID_ELEMENTS},
+    url, Buffer, IntoHtml,
+};
+
+/// Elements whose content is removed along with them when not allowed.
+const DROP_CONTENT: &[&str] = &[
+    "iframe", "noembed", "noframes", "noscript", "object", "script", "style",
+    "template", "textarea", "title", "xmp",
+];
+
+/// Attributes that hold a URL.
+const URL_ATTRIBUTES: &[&str] = &[
+    "action",
+    "background",
+    "cite",
+    "formaction",
+    "href",
+    "longdesc",
+    "poster",
+    "src",
+    "xlink:href",
+];
+
+/// A list of allowed elements, attributes and URL schemes.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    elements: Vec<Cow<'static, str>>,
+    attributes: Vec<Cow<'static, str>>,
+    element_attributes: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    url_schemes: Vec<Cow<'static, str>>,
+    link_rel: Option<Cow<'static, str>>,
+}
+
+impl Policy {
+    /// Creates a policy that allows nothing but text.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            elements: Vec::new(),
+            attributes: Vec::new(),
+            element_attributes: Vec::new(),
+            url_schemes: Vec::new(),
+            link_rel: None,
+        }
+    }
+
+    /// Creates a policy suitable for user comments and other rich text.
+    ///
+    /// It allows basic formatting, lists, quotes, code, tables, links and
+    /// images with `http`, `https` and `mailto` URLs, and adds
+    /// `rel="nofollow noopener noreferrer"` to links.
+    pub fn basic() -> Self {
+        Self::new()
+            .allow_elements([
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "br",
+                "code",
+                "dd",
+                "del",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "ins",
+                "kbd",
+                "li",
+                "mark",
+                "ol",
+                "p",
+                "pre",
+                "q",
+                "s",
+                "small",
+                "span",
+                "strong",
+                "sub",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "tr",
+                "u",
+                "ul",
+            ])
+            .allow_attributes(["title", "lang", "dir"])
+            .allow_element_attributes("a", ["href"])
+            .allow_element_attributes("img", ["src", "alt", "width", "height"])
+            .allow_element_attributes("blockquote", ["cite"])
+            .allow_element_attributes("q", ["cite"])
+            .allow_element_attributes("td", ["colspan", "rowspan"])
+            .allow_element_attributes("th", ["colspan", "rowspan", "scope"])
+            .allow_url_schemes(["http", "https", "mailto"])
+            .link_rel("nofollow noopener noreferrer")
+    }
+
+    /// Allows the given elements.
+    pub fn allow_elements<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'static, str>>,
+    {
+        self.elements.extend(names.into_iter().map(lowercase));
+        self
+    }
+
+    /// Allows the given attributes on every allowed element.
+    pub fn allow_attributes<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'static, str>>,
+    {
+        self.attributes.extend(names.into_iter().map(lowercase));
+        self
+    }
+
+    /// Allows the given attributes on `element`.
+    pub fn allow_element_attributes<I, S>(
+        mut self,
+        element: impl Into<Cow<'static, str>>,
+        names: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'static, str>>,
+    {
+        let element = lowercase(element);
+        self.element_attributes.extend(
+            names
+                .into_iter()
+                .map(|name| (element.clone(), lowercase(name))),
+        );
+        self
+    }
+
+    /// Allows the given URL schemes in URL attributes such as `href` and
+    /// `src`. Relative URLs are always allowed.
+    pub fn allow_url_schemes<I, S>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'static, str>>,
+    {
+        self.url_schemes.extend(schemes.into_iter().map(lowercase));
+        self
+    }
+
+    /// Sets the `rel` attribute of every `a` element, replacing any existing
+    /// value.
+    pub fn link_rel(mut self, rel: impl Into<Cow<'static, str>>) -> Self {
+        self.link_rel = Some(rel.into());
+        self
+    }
+
+    fn is_element_allowed(&self, name: &str) -> bool {
+        contains(&self.elements, name)
+    }
+
+    fn is_attribute_allowed(&self, element: &str, name: &str) -> bool {
+        contains(&self.attributes, name)
+            || self.element_attributes.iter().any(|(el, attr)| {
+                el.eq_ignore_ascii_case(element)
+                    && attr.eq_ignore_ascii_case(name)
+            })
+    }
+
+    fn is_url_allowed(&self, url: &str) -> bool {
+        match url::scheme(url) {
+            Some(scheme) => contains(&self.url_schemes, &scheme),
+            None => true,
+        }
+    }
+
+    /// Sanitizes an HTML fragment.
+    pub fn sanitize(&self, input: &str) -> Sanitized {
+        let mut buf = Buffer::with_capacity(input.len());
+        let mut open: Vec<&str> = Vec::new();
+        // The element whose content is being removed, and its nesting depth.
+        let mut dropping: Option<(&str, usize)> = None;
+
+        for token in Tokenizer::new(input) {
+            if let Some((name, depth)) = &mut dropping {
+                match token {
+                    Token::StartTag(tag)
+                        if tag.name.eq_ignore_ascii_case(name) =>
+                    {
+                        *depth += 1;
+                    }
+                    Token::EndTag(end) if end.eq_ignore_ascii_case(name) => {
+                        if *depth == 0 {
+                            dropping = None;
+                        } else {
+                            *depth -= 1;
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match token {
+                Token::Text(text) => escape_into(&mut buf, &unescape(text)),
+                Token::RawText(_) | Token::Comment(_) | Token::Doctype(_) => {}
+                Token::StartTag(tag) => {
+                    if !self.is_element_allowed(tag.name) {
+                        if contains_str(DROP_CONTENT, tag.name) {
+                            dropping = Some((tag.name, 0));
+                        }
+                        continue;
+                    }
+
+                    buf.push('<');
+                    push_lowercase(&mut buf, tag.name);
+                    for attr in &tag.attrs {
+                        if !self.is_attribute_allowed(tag.name, attr.name)
+                            || (self.link_rel.is_some()
+                                && tag.name.eq_ignore_ascii_case("a")
+                                && attr.name.eq_ignore_ascii_case("rel"))
+                        {
+                            continue;
+                        }
+                        let value = unescape_attr(attr.value);
+                        if contains_str(URL_ATTRIBUTES, attr.name)
+                            && !self.is_url_allowed(&value)
+                        {
+                            continue;
+                        }
+                        buf.push(' ');
+                        push_lowercase(&mut buf, attr.name);
+                        buf.push_str("=\"");
+                        escape_into(&mut buf, &value);
+                        buf.push('"');
+                    }
+                    if let Some(rel) = &self.link_rel {
+                        if tag.name.eq_ignore_ascii_case("a") {
+                            buf.push_str(" rel=\"");
+                            escape_into(&mut buf, rel);
+                            buf.push('"');
+                        }
+                    }
+                    buf.push('>');
+
+                    if !contains_str(VOID_ELEMENTS, tag.name) {
+                        open.push(tag.name);
+                    }
+                }
+                Token::EndTag(name) => {
+                    let Some(i) = open
+                        .iter()
+                        .rposition(|open| open.eq_ignore_ascii_case(name))
+                    else {
+                        continue;
+                    };
+                    for name in open.drain(i..).rev() {
+                        close(&mut buf, name);
+                    }
+                }
+            }
+        }
+
+        for name in open.into_iter().rev() {
+            close(&mut buf, name);
+        }
+
+        Sanitized(buf.into_string())
+    }
+}
+
+/// Sanitized HTML, safe to render without further escaping.
+///
+/// The value is a plain string and can be cloned or cached.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sanitized(String);
+
+impl Sanitized {
+    /// Sanitizes an HTML fragment using [`Policy::basic`].
+    #[inline]
+    pub fn new(input: &str) -> Self {
+        Policy::basic().sanitize(input)
+    }
+
+    /// Returns the sanitized HTML.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the sanitized HTML as a [`String`].
+    #[inline]
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl IntoHtml for Sanitized {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        buf.push_str(&self.0);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl IntoHtml for &Sanitized {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        buf.push_str(&self.0);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+}
+
+fn lowercase<S: Into<Cow<'static, str>>>(s: S) -> Cow<'static, str> {
+    let s = s.into();
+    if s.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(s.to_ascii_lowercase())
+    } else {
+        s
+    }
+}
+
+fn contains(list: &[Cow<'static, str>], name: &str) -> bool {
+    list.iter().any(|item| item.eq_ignore_ascii_case(name))
+}
+
+fn contains_str(list: &[&str], name: &str) -> bool {
+    list.iter().any(|item| item.eq_ignore_ascii_case(name))
+}
+
+fn push_lowercase(buf: &mut Buffer, name: &str) {
+    for c in name.chars() {
+        match c {
+            'A'..='Z' => buf.push(c.to_ascii_lowercase()),
+            '"' | '\'' | '<' | '=' | '`' => {}
+            _ => buf.push(c),
+        }
+    }
+}
+
+fn close(buf: &mut Buffer, name: &str) {
+    buf.push_str("</");
+    push_lowercase(buf, name);
+    buf.push('>');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(input: &str) -> String {
+        Sanitized::new(input).into_string()
+    }
+
+    #[test]
+    fn text_only() {
+        let policy = Policy::new();
+        assert_eq!(
+            policy.sanitize("<b>bold</b> &amp; <i>x < y</i>").as_str(),
+            "bold &amp; x &lt; y"
+        );
+    }
+
+    #[test]
+    fn removes_disallowed_elements() {
+        assert_eq!(basic("<p>a<script>alert(1)</script>b</p>"), "<p>ab</p>");
+        assert_eq!(basic("<p>a<style>*{}</style></p>"), "<p>a</p>");
+        assert_eq!(basic("<form><p>a</p></form>"), "<p>a</p>");
+        assert_eq!(basic("a<!-- c -->b"), "ab");
+        assert_eq!(basic("<svg><p>a</p></svg>"), "<p>a</p>");
+        assert_eq!(basic("<object><object></object>a</object>b"), "b");
+    }
+
+    #[test]
+    fn removes_disallowed_attributes() {
+        assert_eq!(
+            basic("<p onclick=\"x()\" title='t' STYLE=\"a\">a</p>"),
+            "<p title=\"t\">a</p>"
+        );
+        assert_eq!(
+            basic("<img src=x.png onerror=alert(1)>"),
+            "<img src=\"x.png\">"
+        );
+    }
+
+    #[test]
+    fn checks_url_schemes() {
+        assert_eq!(
+            basic("<a href=\"https://example.com/?a=1&amp;b=2\">a</a>"),
+            "<a href=\"https://example.com/?a=1&amp;b=2\" rel=\"nofollow \
+             noopener noreferrer\">a</a>"
+        );
+        assert_eq!(
+            basic("<a href=\"/relative\" rel=\"opener\">a</a>"),
+            "<a href=\"/relative\" rel=\"nofollow noopener noreferrer\">a</a>"
+        );
+        for href in [
+            "javascript:alert(1)",
+            " JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            "javascript&colon;alert(1)",
+            "&#106;avascript:alert(1)",
+            "data:text/html,x",
+        ] {
+            assert_eq!(
+                Policy::new()
+                    .allow_elements(["a"])
+                    .allow_element_attributes("a", ["href"])
+                    .allow_url_schemes(["https"])
+                    .sanitize(&alloc::format!("<a href=\"{href}\">a</a>"))
+                    .as_str(),
+                "<a>a</a>",
+                "{href}"
+            );
+        }
+    }
+
+    #[test]
+    fn balances_tags() {
+        assert_eq!(basic("<p><b>a</p>c</b>"), "<p><b>a</b></p>c");
+        assert_eq!(basic("<ul><li>a<li>b"), "<ul><li>a<li>b</li></li></ul>");
+        assert_eq!(basic("</p>a<br></br>"), "a<br>");
+    }
+
+    #[test]
+    fn escapes_output() {
+        assert_eq!(
+            basic("<p title=\"a&quot;b\">\"quoted\" &lt;tag&gt;</p>"),
+            "<p title=\"a&quot;b\">&quot;quoted&quot; &lt;tag&gt;</p>"
+        );
+    }
+}
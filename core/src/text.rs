@@ -0,0 +1,494 @@
+//! Plain-text extraction.
+//!
+//! The text content of a template is extracted while it renders: tags are
+//! skipped, character references in markup are decoded and whitespace is
+//! collapsed like a browser would, while dynamic text is written as is,
+//! without being escaped first. The content of `head`, `title`, `template`,
+//! `script` and `style` elements is left out.
+//!
+//! ```
+//! # use vy::{prelude::*, text::TextOptions};
+//! let article = || {
+//!     article!(
+//!         h1!("Fish & Chips"),
+//!         p!("A  classic\n dish.", br!(), "Serves 2."),
+//!         ul!(li!("Fish"), li!("Potatoes"))
+//!     )
+//! };
+//!
+//! assert_eq!(
+//!     article().into_text(),
+//!     "Fish & Chips A classic dish. Serves 2. Fish Potatoes"
+//! );
+//! assert_eq!(
+//!     TextOptions::new().block_newlines(true).render(article()),
+//!     "Fish & Chips\n\nA classic dish.\nServes 2.\n\nFish\nPotatoes"
+//! );
+//! ```
+
+use alloc::{boxed::Box, string::String};
+
+use crate::{
+    buffer::{Buffer, Filter},
+    escape::{unescape, PreEscaped},
+    IntoHtml,
+};
+
+/// Elements whose content is not part of the text.
+const HIDDEN: &[&str] = &["head", "template", "title"];
+
+/// Elements whose content is not markup, and is not part of the text.
+const RAW_TEXT: &[&str] = &["script", "style"];
+
+/// Elements that are separated from the surrounding text by a blank line.
+const PARAGRAPHS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6", "p"];
+
+/// Elements that start on a new line.
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "caption",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "hgroup",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Elements that are separated from the surrounding text by a space.
+const CELLS: &[&str] = &["td", "th"];
+
+/// A pending separator, ordered by strength.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Separator {
+    None,
+    Space,
+    Newline,
+    BlankLine,
+}
+
+/// Options for extracting plain text from HTML.
+#[derive(Clone, Debug, Default)]
+pub struct TextOptions {
+    block_newlines: bool,
+}
+
+impl TextOptions {
+    /// Creates the default options, which put the whole text on one line.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            block_newlines: false,
+        }
+    }
+
+    /// Sets whether block elements and `br` start a new line, and paragraphs
+    /// and headings are separated by a blank line.
+    #[inline]
+    pub const fn block_newlines(mut self, enabled: bool) -> Self {
+        self.block_newlines = enabled;
+        self
+    }
+
+    /// Renders `html` and extracts its text.
+    pub fn render(&self, html: impl IntoHtml) -> String {
+        let mut buf = Buffer::new();
+        buf.set_filter(Some(Filter::Text(Box::new(TextWriter::new(
+            self.clone(),
+        )))));
+        html.render_into(&mut buf);
+        buf.set_filter(None);
+        buf.into_string()
+    }
+
+    /// Extracts the text of an HTML fragment.
+    #[inline]
+    pub fn extract(&self, html: &str) -> String {
+        self.render(PreEscaped(html))
+    }
+
+    /// Returns the separator around an element.
+    fn separator(&self, name: &str) -> Separator {
+        let block = if is_one_of(name, PARAGRAPHS) {
+            Separator::BlankLine
+        } else if is_one_of(name, BLOCKS) {
+            Separator::Newline
+        } else if is_one_of(name, CELLS) {
+            return Separator::Space;
+        } else {
+            return Separator::None;
+        };
+
+        if self.block_newlines {
+            block
+        } else {
+            Separator::Space
+        }
+    }
+}
+
+/// The lexer state of a [`TextWriter`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    /// After `<`, or `</` for an end tag.
+    TagOpen,
+    TagName,
+    /// Inside a tag after its name, with the open quote, if any, and whether
+    /// the previous byte was a `/`.
+    Tag {
+        quote: Option<u8>,
+        slash: bool,
+    },
+    /// After `<!`, with the number of leading dashes.
+    Declaration {
+        dashes: u8,
+    },
+    /// Inside a comment, with the number of dashes just read.
+    Comment {
+        dashes: u8,
+    },
+    /// Inside a doctype, processing instruction or malformed tag.
+    Bogus,
+    /// Inside a `script` or `style` element, with the number of bytes of its
+    /// end tag matched so far.
+    RawText {
+        matched: usize,
+    },
+}
+
+/// Extracts the text of markup as it is written into a buffer.
+///
+/// Markup is lexed incrementally, so that a tag may be split across writes,
+/// and text written while inside a tag or a hidden element is dropped.
+pub(crate) struct TextWriter {
+    options: TextOptions,
+    state: State,
+    /// The lowercase name of the current tag, truncated to its capacity.
+    name: [u8; 16],
+    name_len: usize,
+    closing: bool,
+    hidden: usize,
+    pre: usize,
+    pending: Separator,
+    started: bool,
+}
+
+impl TextWriter {
+    fn new(options: TextOptions) -> Self {
+        Self {
+            options,
+            state: State::Text,
+            name: [0; 16],
+            name_len: 0,
+            closing: false,
+            hidden: 0,
+            pre: 0,
+            pending: Separator::None,
+            started: false,
+        }
+    }
+
+    /// Writes the text content of `html` into `buf`.
+    pub(crate) fn markup(&mut self, buf: &mut Buffer, html: &str) {
+        let bytes = html.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if self.state == State::Text {
+                let end = html[i..].find('<').map_or(html.len(), |j| i + j);
+                if self.hidden == 0 {
+                    self.emit(buf, &unescape(&html[i..end]));
+                }
+                if end < html.len() {
+                    self.state = State::TagOpen;
+                    self.closing = false;
+                }
+                i = end + 1;
+                continue;
+            }
+
+            let b = bytes[i];
+            i += 1;
+            self.state = match self.state {
+                State::Text => unreachable!(),
+                State::TagOpen => match b {
+                    b'/' if !self.closing => {
+                        self.closing = true;
+                        State::TagOpen
+                    }
+                    b'!' if !self.closing => State::Declaration { dashes: 0 },
+                    _ if b.is_ascii_alphabetic() => {
+                        self.name_len = 0;
+                        i -= 1;
+                        State::TagName
+                    }
+                    _ if self.closing => State::Bogus,
+                    b'?' => State::Bogus,
+                    _ => {
+                        if self.hidden == 0 {
+                            self.emit(buf, "<");
+                        }
+                        i -= 1;
+                        State::Text
+                    }
+                },
+                State::TagName => match b {
+                    b'\t' | b'\n' | b'\x0c' | b'\r' | b' ' | b'/' | b'>' => {
+                        i -= 1;
+                        State::Tag {
+                            quote: None,
+                            slash: false,
+                        }
+                    }
+                    _ => {
+                        if let Some(c) = self.name.get_mut(self.name_len) {
+                            *c = b.to_ascii_lowercase();
+                            self.name_len += 1;
+                        }
+                        State::TagName
+                    }
+                },
+                State::Tag {
+                    quote: Some(quote), ..
+                } => State::Tag {
+                    quote: (b != quote).then_some(quote),
+                    slash: false,
+                },
+                State::Tag { quote: None, slash } => match b {
+                    b'>' => self.end_of_tag(slash),
+                    b'"' | b'\'' => State::Tag {
+                        quote: Some(b),
+                        slash: false,
+                    },
+                    _ => State::Tag {
+                        quote: None,
+                        slash: b == b'/',
+                    },
+                },
+                State::Declaration { dashes } => match b {
+                    b'-' if dashes == 1 => State::Comment { dashes: 0 },
+                    b'-' if dashes == 0 => State::Declaration { dashes: 1 },
+                    b'>' => State::Text,
+                    _ => State::Bogus,
+                },
+                State::Comment { dashes } => match b {
+                    b'-' => State::Comment {
+                        dashes: dashes.saturating_add(1),
+                    },
+                    b'>' if dashes >= 2 => State::Text,
+                    _ => State::Comment { dashes: 0 },
+                },
+                State::Bogus => match b {
+                    b'>' => State::Text,
+                    _ => State::Bogus,
+                },
+                State::RawText { matched } => {
+                    let expected = match matched {
+                        0 => b'<',
+                        1 => b'/',
+                        _ => self.name[matched - 2],
+                    };
+                    if b.to_ascii_lowercase() == expected {
+                        if matched + 1 == self.name_len + 2 {
+                            self.closing = true;
+                            State::Tag {
+                                quote: None,
+                                slash: false,
+                            }
+                        } else {
+                            State::RawText {
+                                matched: matched + 1,
+                            }
+                        }
+                    } else {
+                        State::RawText {
+                            matched: (b == b'<') as usize,
+                        }
+                    }
+                }
+            };
+        }
+    }
+
+    /// Writes text that is not markup into `buf`, unless it lies inside a tag
+    /// or a hidden element.
+    pub(crate) fn text(&mut self, buf: &mut Buffer, text: &str) {
+        if self.state == State::Text && self.hidden == 0 {
+            self.emit(buf, text);
+        }
+    }
+
+    /// Handles the `>` at the end of a tag, returning the next state.
+    fn end_of_tag(&mut self, self_closing: bool) -> State {
+        let name = core::str::from_utf8(&self.name[..self.name_len])
+            .unwrap_or_default();
+        if self.closing {
+            if is_one_of(name, HIDDEN) {
+                self.hidden = self.hidden.saturating_sub(1);
+            } else if !is_one_of(name, RAW_TEXT) {
+                if name == "pre" {
+                    self.pre = self.pre.saturating_sub(1);
+                }
+                self.separate(self.options.separator(name));
+            }
+            return State::Text;
+        }
+
+        if is_one_of(name, RAW_TEXT) && !self_closing {
+            return State::RawText { matched: 0 };
+        }
+        if is_one_of(name, HIDDEN) && !self_closing {
+            self.hidden += 1;
+        } else if name == "br" {
+            self.separate(if self.options.block_newlines {
+                match self.pending {
+                    Separator::Newline | Separator::BlankLine => {
+                        Separator::BlankLine
+                    }
+                    _ => Separator::Newline,
+                }
+            } else {
+                Separator::Space
+            });
+        } else {
+            if name == "pre" {
+                self.pre += 1;
+            }
+            self.separate(self.options.separator(name));
+        }
+        State::Text
+    }
+
+    #[inline]
+    fn separate(&mut self, separator: Separator) {
+        self.pending = self.pending.max(separator);
+    }
+
+    /// Writes `text`, collapsing its whitespace outside of `pre` elements.
+    fn emit(&mut self, buf: &mut Buffer, text: &str) {
+        if self.pre > 0 {
+            if !text.is_empty() {
+                self.word(buf, text);
+            }
+            return;
+        }
+        for (i, word) in text.split(is_whitespace).enumerate() {
+            if i > 0 {
+                self.separate(Separator::Space);
+            }
+            if !word.is_empty() {
+                self.word(buf, word);
+            }
+        }
+    }
+
+    /// Writes the pending separator, if any, and `word`.
+    fn word(&mut self, buf: &mut Buffer, word: &str) {
+        if self.started {
+            buf.push_str(match self.pending {
+                Separator::None => "",
+                Separator::Space => " ",
+                Separator::Newline => "\n",
+                Separator::BlankLine => "\n\n",
+            });
+        }
+        self.pending = Separator::None;
+        self.started = true;
+        buf.push_str(word);
+    }
+}
+
+#[inline]
+fn is_whitespace(ch: char) -> bool {
+    matches!(ch, '\t' | '\n' | '\x0c' | '\r' | ' ')
+}
+
+#[inline]
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(html: &str) -> String {
+        TextOptions::new().extract(html)
+    }
+
+    fn lines(html: &str) -> String {
+        TextOptions::new().block_newlines(true).extract(html)
+    }
+
+    #[test]
+    fn collapse_whitespace() {
+        assert_eq!(text("  a \n\t b  <b> c </b>d "), "a b c d");
+        assert_eq!(text("a&nbsp;&nbsp;b &lt;c&gt;"), "a\u{a0}\u{a0}b <c>");
+        assert_eq!(text("<p>a</p><p>b</p>"), "a b");
+        assert_eq!(text("a<i>b</i>c"), "abc");
+    }
+
+    #[test]
+    fn hidden_content() {
+        assert_eq!(
+            text(
+                "<head><title>T</title><style>p{}</style></\
+                 head><body><script>x<y</script>a<template>b</template></body>"
+            ),
+            "a"
+        );
+    }
+
+    #[test]
+    fn block_newlines() {
+        assert_eq!(
+            lines("<div>a<div>b</div></div><p>c<br>d<br><br>e</p>f"),
+            "a\nb\n\nc\nd\n\ne\n\nf"
+        );
+        assert_eq!(
+            lines(
+                "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td></tr></\
+                 table>"
+            ),
+            "a b\nc"
+        );
+        assert_eq!(lines("<pre>  a\n  b</pre>c"), "  a\n  b\nc");
+    }
+
+    #[test]
+    fn split_markup() {
+        let html = (
+            PreEscaped("<p title=\"a>"),
+            "b\"",
+            PreEscaped("\">x &amp; <"),
+            PreEscaped("!-- c --"),
+            PreEscaped("> y</p><scr"),
+            PreEscaped("ipt>\"</"),
+            PreEscaped("script>\"</SCRIPT >"),
+            "<i> &amp;",
+            PreEscaped("<b>&amp; 1<2</b>"),
+        );
+        assert_eq!(TextOptions::new().render(html), "x & y \"<i> &amp;& 1<2");
+    }
+}
@@ -0,0 +1,383 @@
+//! A small, forgiving HTML tokenizer.
+//!
+//! This is not a full implementation of the HTML parsing algorithm, but it
+//! follows the same tokenization rules for tags, attributes, comments and raw
+//! text elements, which is enough to sanitize and reformat HTML fragments.
+
+use alloc::vec::Vec;
+
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    /// Text with character references still encoded.
+    Text(&'a str),
+    /// The content of a `script`, `style` or similar element, which must not
+    /// be decoded.
+    RawText(&'a str),
+    StartTag(Tag<'a>),
+    EndTag(&'a str),
+    Comment(&'a str),
+    Doctype(&'a str),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub attrs: Vec<Attr<'a>>,
+    pub self_closing: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Attr<'a> {
+    pub name: &'a str,
+    /// The value with character references still encoded.
+    pub value: &'a str,
+}
+
+/// Elements that never have content or an end tag.
+pub const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta",
+    "source", "track", "wbr",
+];
+
+/// Elements whose content is not parsed as markup.
+const RAW_TEXT: &[&str] = &[
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+/// Elements whose raw content still contains character references.
+const ESCAPABLE_RAW_TEXT: &[&str] = &["textarea", "title"];
+
+pub struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+    raw_text: Option<&'a str>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            raw_text: None,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn raw_text(&mut self, name: &'a str) -> Token<'a> {
+        let rest = self.rest();
+        let bytes = rest.as_bytes();
+        let mut end = rest.len();
+        let mut i = 0;
+        while let Some(j) = rest[i..].find("</") {
+            let start = i + j;
+            let after = start + 2 + name.len();
+            if after <= rest.len()
+                && rest.is_char_boundary(after)
+                && rest[start + 2..after].eq_ignore_ascii_case(name)
+                && matches!(
+                    bytes.get(after),
+                    None | Some(
+                        b'\t' | b'\n' | b'\x0c' | b'\r' | b' ' | b'/' | b'>'
+                    )
+                )
+            {
+                end = start;
+                break;
+            }
+            i = start + 2;
+        }
+        self.pos += end;
+        let text = &rest[..end];
+        if ESCAPABLE_RAW_TEXT
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(name))
+        {
+            Token::Text(text)
+        } else {
+            Token::RawText(text)
+        }
+    }
+
+    fn tag(&mut self) -> Option<Token<'a>> {
+        let rest = self.rest();
+        let bytes = rest.as_bytes();
+        let end_tag = bytes[1] == b'/';
+        let mut i = if end_tag { 2 } else { 1 };
+
+        let name_start = i;
+        while i < bytes.len() && !is_tag_delimiter(bytes[i]) {
+            i += 1;
+        }
+        let name = &rest[name_start..i];
+
+        let mut attrs = Vec::new();
+        let mut self_closing = false;
+        loop {
+            while i < bytes.len()
+                && (is_whitespace(bytes[i]) || bytes[i] == b'/')
+            {
+                self_closing = bytes[i] == b'/';
+                i += 1;
+            }
+            match bytes.get(i) {
+                // A tag that is not closed before the end is dropped.
+                None => {
+                    self.pos = self.input.len();
+                    return None;
+                }
+                Some(b'>') => {
+                    i += 1;
+                    break;
+                }
+                _ => {}
+            }
+            self_closing = false;
+
+            let attr_start = i;
+            i += 1;
+            while i < bytes.len()
+                && !is_tag_delimiter(bytes[i])
+                && bytes[i] != b'='
+            {
+                i += 1;
+            }
+            let attr_name = &rest[attr_start..i];
+
+            while i < bytes.len() && is_whitespace(bytes[i]) {
+                i += 1;
+            }
+            let mut value = "";
+            if bytes.get(i) == Some(&b'=') {
+                i += 1;
+                while i < bytes.len() && is_whitespace(bytes[i]) {
+                    i += 1;
+                }
+                match bytes.get(i) {
+                    Some(&quote @ (b'"' | b'\'')) => {
+                        let start = i + 1;
+                        let Some(len) = rest[start..].find(quote as char)
+                        else {
+                            self.pos = self.input.len();
+                            return None;
+                        };
+                        value = &rest[start..start + len];
+                        i = start + len + 1;
+                    }
+                    _ => {
+                        let start = i;
+                        while i < bytes.len()
+                            && !is_whitespace(bytes[i])
+                            && bytes[i] != b'>'
+                        {
+                            i += 1;
+                        }
+                        value = &rest[start..i];
+                    }
+                }
+            }
+
+            if !end_tag {
+                attrs.push(Attr {
+                    name: attr_name,
+                    value,
+                });
+            }
+        }
+
+        self.pos += i;
+
+        if end_tag {
+            return Some(Token::EndTag(name));
+        }
+
+        if RAW_TEXT.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            self.raw_text = Some(name);
+        }
+
+        Some(Token::StartTag(Tag {
+            name,
+            attrs,
+            self_closing,
+        }))
+    }
+
+    /// Consumes a comment-like construct ending with `terminator`, returning
+    /// its content.
+    fn until(&mut self, skip: usize, terminator: &str) -> &'a str {
+        let rest = &self.rest()[skip..];
+        match rest.find(terminator) {
+            Some(i) => {
+                self.pos += skip + i + terminator.len();
+                &rest[..i]
+            }
+            None => {
+                self.pos = self.input.len();
+                rest
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(name) = self.raw_text.take() {
+                let token = self.raw_text(name);
+                if !matches!(token, Token::Text("") | Token::RawText("")) {
+                    return Some(token);
+                }
+            }
+
+            let rest = self.rest();
+            if rest.is_empty() {
+                return None;
+            }
+
+            let bytes = rest.as_bytes();
+            if bytes[0] != b'<' {
+                let len = rest.find('<').unwrap_or(rest.len());
+                self.pos += len;
+                return Some(Token::Text(&rest[..len]));
+            }
+
+            match bytes.get(1) {
+                Some(b) if b.is_ascii_alphabetic() => match self.tag() {
+                    Some(token) => return Some(token),
+                    None => continue,
+                },
+                Some(b'/') => match bytes.get(2) {
+                    Some(b) if b.is_ascii_alphabetic() => match self.tag() {
+                        Some(token) => return Some(token),
+                        None => continue,
+                    },
+                    Some(b'>') => {
+                        self.pos += 3;
+                        continue;
+                    }
+                    None => {
+                        self.pos += 2;
+                        return Some(Token::Text("</"));
+                    }
+                    _ => return Some(Token::Comment(self.until(2, ">"))),
+                },
+                Some(b'!') if rest.starts_with("<!--") => {
+                    return Some(Token::Comment(self.until(4, "-->")));
+                }
+                Some(b'!')
+                    if rest.len() >= 9
+                        && rest.is_char_boundary(9)
+                        && rest[2..9].eq_ignore_ascii_case("doctype") =>
+                {
+                    return Some(Token::Doctype(self.until(9, ">").trim()));
+                }
+                Some(b'!' | b'?') => {
+                    return Some(Token::Comment(self.until(2, ">")));
+                }
+                _ => {
+                    self.pos += 1;
+                    return Some(Token::Text("<"));
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+#[inline]
+fn is_tag_delimiter(b: u8) -> bool {
+    is_whitespace(b) || b == b'/' || b == b'>'
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<Token<'_>> {
+        Tokenizer::new(input).collect()
+    }
+
+    #[test]
+    fn tags_and_text() {
+        assert_eq!(
+            tokens("<p class=a id='b' hidden>x &amp; y</P><br/>"),
+            vec![
+                Token::StartTag(Tag {
+                    name: "p",
+                    attrs: vec![
+                        Attr {
+                            name: "class",
+                            value: "a"
+                        },
+                        Attr {
+                            name: "id",
+                            value: "b"
+                        },
+                        Attr {
+                            name: "hidden",
+                            value: ""
+                        },
+                    ],
+                    self_closing: false,
+                }),
+                Token::Text("x &amp; y"),
+                Token::EndTag("P"),
+                Token::StartTag(Tag {
+                    name: "br",
+                    attrs: vec![],
+                    self_closing: true,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_text() {
+        assert_eq!(
+            tokens("<script>a</b>c</script >d"),
+            vec![
+                Token::StartTag(Tag {
+                    name: "script",
+                    attrs: vec![],
+                    self_closing: false,
+                }),
+                Token::RawText("a</b>c"),
+                Token::EndTag("script"),
+                Token::Text("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_and_malformed() {
+        assert_eq!(
+            tokens("<!DOCTYPE html><!-- c -->a < b<? x ><p"),
+            vec![
+                Token::Doctype("html"),
+                Token::Comment(" c "),
+                Token::Text("a "),
+                Token::Text("<"),
+                Token::Text(" b"),
+                Token::Comment(" x "),
+            ]
+        );
+    }
+}
@@ -0,0 +1,759 @@
+//! Safe URLs for attributes such as `href` and `src`.
+//!
+//! Escaping alone does not make a URL safe: `javascript:alert(1)` contains no
+//! special characters. The element macros therefore check the rendered value
+//! of every dynamic URL attribute (`href`, `src`, `action`, `formaction`,
+//! `poster`, `srcset`, ...) and replace URLs with a dangerous scheme by
+//! [`INVALID_URL`]. Static string literals are left untouched.
+//!
+//! ```
+//! # use vy::prelude::*;
+//! let user_url = "javascript:alert(1)";
+//! assert_eq!(
+//!     a!(href = user_url, "home").into_string(),
+//!     "<a href=\"about:invalid#vy-unsafe-url\">home</a>"
+//! );
+//! ```
+//!
+//! Use [`SafeUrl`] to validate a URL ahead of time, for example when it is
+//! received from a user.
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{cmp, error::Error as StdError, fmt, fmt::Write};
+
+#[cfg(feature = "alloc")]
+use crate::escape::{escape_into, escaped_len};
+use crate::{
+    escape::{escape_char, escape_extra, EscapePolicy},
+    helpers::Counter,
+    Buffer, IntoHtml,
+};
+
+/// The URL written in place of a URL with a dangerous scheme.
+pub const INVALID_URL: &str = "about:invalid#vy-unsafe-url";
+
+/// Image types that are safe to load from a `data:` URL.
+const SAFE_DATA_IMAGES: &[&str] =
+    &["avif", "bmp", "gif", "jpeg", "jpg", "png", "webp"];
+
+enum Scheme<'a> {
+    /// A relative URL, without a scheme.
+    Relative,
+    /// An absolute URL, with the raw scheme and the part after the colon.
+    Absolute(&'a str, &'a str),
+    /// The URL contains a character reference where the scheme may be.
+    Ambiguous,
+}
+
+/// Parses the scheme of a URL like browsers do, ignoring leading
+/// whitespace and control characters as well as tabs and newlines.
+///
+/// If `escaped` is true, the URL is HTML-escaped and any character
+/// reference in the scheme makes it [`Scheme::Ambiguous`].
+fn parse_scheme(url: &str, escaped: bool) -> Scheme<'_> {
+    let url = url.trim_start_matches(|c: char| c <= ' ');
+    let mut letters = 0;
+    for (i, b) in url.bytes().enumerate() {
+        match b {
+            b':' if letters > 0 => {
+                return Scheme::Absolute(&url[..i], &url[i + 1..]);
+            }
+            b'\t' | b'\n' | b'\r' => {}
+            b'a'..=b'z' | b'A'..=b'Z' => letters += 1,
+            b'0'..=b'9' | b'+' | b'-' | b'.' if letters > 0 => {}
+            b'&' if escaped => return Scheme::Ambiguous,
+            _ => return Scheme::Relative,
+        }
+    }
+    Scheme::Relative
+}
+
+/// Returns whether the raw `scheme` equals `name`, ignoring ASCII case, tabs
+/// and newlines.
+fn scheme_is(scheme: &str, name: &str) -> bool {
+    scheme
+        .bytes()
+        .filter(|b| !matches!(b, b'\t' | b'\n' | b'\r'))
+        .map(|b| b.to_ascii_lowercase())
+        .eq(name.bytes())
+}
+
+fn is_safe_scheme(scheme: Scheme) -> bool {
+    match scheme {
+        Scheme::Relative => true,
+        Scheme::Ambiguous => false,
+        Scheme::Absolute(scheme, rest) => {
+            if scheme_is(scheme, "javascript") || scheme_is(scheme, "vbscript")
+            {
+                false
+            } else if scheme_is(scheme, "data") {
+                let Some(media_type) = rest
+                    .get(..6)
+                    .filter(|s| s.eq_ignore_ascii_case("image/"))
+                    .map(|_| &rest[6..])
+                else {
+                    return false;
+                };
+                let end =
+                    media_type.find([';', ',']).unwrap_or(media_type.len());
+                SAFE_DATA_IMAGES
+                    .iter()
+                    .any(|ty| media_type[..end].eq_ignore_ascii_case(ty))
+            } else {
+                true
+            }
+        }
+    }
+}
+
+/// Returns the lowercased scheme of `url`, or `None` if it is relative.
+#[cfg(feature = "sanitize")]
+pub(crate) fn scheme(url: &str) -> Option<String> {
+    match parse_scheme(url, false) {
+        Scheme::Absolute(scheme, _) => Some(
+            scheme
+                .chars()
+                .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+                .map(|c| c.to_ascii_lowercase())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `url` is relative or has a scheme that cannot execute
+/// code.
+///
+/// The `javascript:` and `vbscript:` schemes are rejected, as are `data:`
+/// URLs, except for common raster image types.
+///
+/// ```
+/// # use vy::url::is_safe;
+/// assert!(is_safe("/users?id=1"));
+/// assert!(is_safe("https://example.com"));
+/// assert!(is_safe("data:image/png;base64,iVBORw0KGgo="));
+/// assert!(!is_safe(" JavaScript:alert(1)"));
+/// assert!(!is_safe("data:text/html,<script>alert(1)</script>"));
+/// ```
+pub fn is_safe(url: &str) -> bool {
+    is_safe_scheme(parse_scheme(url, false))
+}
+
+/// Returns whether `b` must be percent-encoded in a URL attribute.
+#[inline]
+fn needs_encoding(b: u8) -> bool {
+    b <= b' ' || b >= 0x7f || matches!(b, b'"' | b'<' | b'>' | b'`')
+}
+
+/// Writes `input` into `out`, percent-encoding the bytes for which `encode`
+/// returns `true`.
+///
+/// `encode` must return `true` for all non-ASCII bytes.
+pub(crate) fn percent_encode_into(
+    out: &mut (impl Write + ?Sized),
+    input: &str,
+    encode: impl Fn(u8) -> bool,
+) {
+    let mut last = 0;
+    for (i, b) in input.bytes().enumerate() {
+        if encode(b) {
+            debug_assert!(b.is_ascii() || encode(0x80));
+            if last < i {
+                _ = out.write_str(&input[last..i]);
+            }
+            let encoded = percent_encode_byte(b);
+            // SAFETY: the encoded byte is ASCII.
+            _ = out
+                .write_str(unsafe { core::str::from_utf8_unchecked(&encoded) });
+            last = i + 1;
+        }
+    }
+    _ = out.write_str(&input[last..]);
+}
+
+/// Returns the percent-encoded form of `b`.
+#[inline]
+fn percent_encode_byte(b: u8) -> [u8; 3] {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    [b'%', HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]
+}
+
+/// Decodes the percent-encoded bytes in `input`, returning `None` if the
+/// result is not valid UTF-8.
+#[cfg(feature = "alloc")]
+pub(crate) fn percent_decode(input: &str) -> Option<Cow<'_, str>> {
+    if !input.contains('%') {
+        return Some(Cow::Borrowed(input));
+    }
+
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = match bytes[i..] {
+            [b'%', hi, lo, ..] => hex(hi).zip(hex(lo)),
+            _ => None,
+        };
+        match decoded {
+            Some((hi, lo)) => {
+                out.push(hi << 4 | lo);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok().map(Cow::Owned)
+}
+
+/// Returns `input` if it contains no percent-encoded bytes, which cannot be
+/// decoded without allocating.
+#[cfg(not(feature = "alloc"))]
+pub(crate) fn percent_decode(input: &str) -> Option<&str> {
+    (!input.contains('%')).then_some(input)
+}
+
+/// The error returned when creating a [`SafeUrl`] from a URL with a dangerous
+/// scheme.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsafeUrl;
+
+impl fmt::Display for UnsafeUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("URL has an unsafe scheme")
+    }
+}
+
+impl StdError for UnsafeUrl {}
+
+/// A URL that is known to be safe to use in a URL attribute.
+///
+/// Spaces, control characters, non-ASCII characters and characters that
+/// are not allowed in URLs are percent-encoded.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SafeUrl(Cow<'static, str>);
+
+#[cfg(feature = "alloc")]
+impl SafeUrl {
+    /// Validates `url`, returning an error if its scheme is unsafe according
+    /// to [`is_safe`].
+    ///
+    /// ```
+    /// # use vy::{prelude::*, url::SafeUrl};
+    /// let url = SafeUrl::new("/search?q=vy html").unwrap();
+    /// assert_eq!(url.as_str(), "/search?q=vy%20html");
+    /// assert!(SafeUrl::new("javascript:alert(1)").is_err());
+    /// ```
+    pub fn new(url: impl Into<Cow<'static, str>>) -> Result<Self, UnsafeUrl> {
+        let url = url.into();
+        if !is_safe(&url) {
+            return Err(UnsafeUrl);
+        }
+
+        let trimmed = url.trim_matches(|c: char| c <= ' ');
+        if trimmed.len() == url.len() && !url.bytes().any(needs_encoding) {
+            return Ok(Self(url));
+        }
+
+        let mut encoded = String::with_capacity(trimmed.len());
+        percent_encode_into(&mut encoded, trimmed, needs_encoding);
+        Ok(Self(Cow::Owned(encoded)))
+    }
+
+    /// Returns the URL as a string slice.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Display for SafeUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl IntoHtml for SafeUrl {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        escape_into(buf, &self.0);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        escaped_len(&self.0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl IntoHtml for &SafeUrl {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        escape_into(buf, &self.0);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        escaped_len(&self.0)
+    }
+}
+
+/// The value of a URL attribute, written by the element macros.
+///
+/// The rendered value is replaced by [`INVALID_URL`] if it has an unsafe
+/// scheme, and percent-encoded where needed.
+#[doc(hidden)]
+pub struct UrlAttr<T>(pub T);
+
+impl<T: IntoHtml> IntoHtml for UrlAttr<T> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        // Check the URL with minimal escaping, so that character references
+        // in the scheme only come from the value itself.
+        let start = buf.len();
+        let hold = buf.hold(start);
+        let policy = buf.set_escape_policy(EscapePolicy::Minimal);
+        self.0.escape_and_write(buf);
+        buf.set_escape_policy(policy);
+
+        let url = &buf.as_str()[start..];
+        if !is_safe_scheme(parse_scheme(url, true)) {
+            buf.truncate(start);
+            buf.release(hold);
+            buf.push_str(INVALID_URL);
+            return;
+        }
+
+        let is_space = |c: char| c <= ' ';
+        let end = start + url.trim_end_matches(is_space).len();
+        let lead = url.len() - url.trim_start_matches(is_space).len();
+        buf.truncate(cmp::max(end, start + lead));
+        buf.remove(start, start + lead);
+        buf.replace_chars(start, |ch, out| {
+            let mut bytes = [0; 4];
+            let bytes = ch.encode_utf8(&mut bytes).as_bytes();
+            if !needs_encoding(bytes[0]) {
+                return None;
+            }
+            for (i, &b) in bytes.iter().enumerate() {
+                out[i * 3..i * 3 + 3].copy_from_slice(&percent_encode_byte(b));
+            }
+            Some(bytes.len() * 3)
+        });
+        escape_extra(buf, start);
+        buf.release(hold);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.size_hint()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.0.exact_size()
+    }
+}
+
+/// The value of a `srcset` attribute, written by the element macros.
+///
+/// The whole value is removed if any of its URLs has an unsafe scheme.
+#[doc(hidden)]
+pub struct SrcsetAttr<T>(pub T);
+
+impl<T: IntoHtml> IntoHtml for SrcsetAttr<T> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let start = buf.len();
+        let hold = buf.hold(start);
+        let policy = buf.set_escape_policy(EscapePolicy::Minimal);
+        self.0.escape_and_write(buf);
+        buf.set_escape_policy(policy);
+
+        let srcset = &buf.as_str()[start..];
+        let safe = srcset.split(',').all(|candidate| {
+            let url = candidate.split_ascii_whitespace().next().unwrap_or("");
+            is_safe_scheme(parse_scheme(url, true))
+        });
+        if safe {
+            escape_extra(buf, start);
+        } else {
+            buf.truncate(start);
+        }
+        buf.release(hold);
+    }
+
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.0.size_hint()
+    }
+
+    #[inline]
+    fn exact_size(&self) -> usize {
+        self.0.exact_size()
+    }
+}
+
+/// A URL built by the `url!` macro.
+///
+/// Path segments and query values are percent-encoded as they are written,
+/// so the URL can be rendered in an attribute or formatted with [`Display`]
+/// without intermediate allocations.
+///
+/// ```
+/// # use vy::prelude::*;
+/// let id = 42;
+/// let query = "rust & html";
+/// let href = url!("/users/{id}/posts"; page = 2, q = query);
+/// assert_eq!(href.to_string(), "/users/42/posts?page=2&q=rust%20%26%20html");
+/// assert_eq!(
+///     a!(href = href, "Posts").into_string(),
+///     "<a href=\"/users/42/posts?page=2&amp;q=rust%20%26%20html\">Posts</a>"
+/// );
+/// ```
+///
+/// [`Display`]: fmt::Display
+pub struct Url<F>(F);
+
+impl<F: Fn(&mut UrlWriter)> Url<F> {
+    #[doc(hidden)]
+    #[inline]
+    pub const fn new(f: F) -> Self {
+        Self(f)
+    }
+
+    fn write(&self, out: &mut dyn Write, html: bool) {
+        (self.0)(&mut UrlWriter {
+            out,
+            html,
+            query: false,
+        });
+    }
+}
+
+impl<F: Fn(&mut UrlWriter)> fmt::Display for Url<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, false);
+        Ok(())
+    }
+}
+
+impl<F: Fn(&mut UrlWriter)> IntoHtml for Url<F> {
+    #[inline]
+    fn into_html(self) -> impl IntoHtml {
+        self
+    }
+
+    #[inline]
+    fn escape_and_write(self, buf: &mut Buffer) {
+        let start = buf.len();
+        let hold = buf.hold(start);
+        self.write(buf, true);
+        escape_extra(buf, start);
+        buf.release(hold);
+    }
+
+    fn size_hint(&self) -> usize {
+        let mut counter = Counter(0);
+        self.write(&mut counter, true);
+        counter.0
+    }
+}
+
+/// Percent-encodes everything written to it.
+struct Encoder<'a, 'b> {
+    out: &'a mut (dyn Write + 'b),
+    encode: fn(u8) -> bool,
+}
+
+impl Write for Encoder<'_, '_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        percent_encode_into(self.out, s, self.encode);
+        Ok(())
+    }
+}
+
+/// Returns whether `b` must be percent-encoded in a path segment.
+#[inline]
+fn segment_needs_encoding(b: u8) -> bool {
+    !(b.is_ascii_alphanumeric()
+        || matches!(
+            b,
+            b'-' | b'.'
+                | b'_'
+                | b'~'
+                | b'!'
+                | b'$'
+                | b'('
+                | b')'
+                | b'*'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b':'
+                | b'@'
+        ))
+}
+
+/// Returns whether `b` must be percent-encoded in a query name or value.
+#[inline]
+fn query_needs_encoding(b: u8) -> bool {
+    !(b.is_ascii_alphanumeric()
+        || matches!(
+            b,
+            b'-' | b'.'
+                | b'_'
+                | b'~'
+                | b'!'
+                | b'$'
+                | b'('
+                | b')'
+                | b'*'
+                | b','
+                | b';'
+                | b':'
+                | b'@'
+                | b'/'
+                | b'?'
+        ))
+}
+
+/// Writes the parts of a [`Url`], used by the `url!` macro and by
+/// [`Route`](crate::route::Route) implementations.
+pub struct UrlWriter<'a> {
+    out: &'a mut dyn Write,
+    html: bool,
+    query: bool,
+}
+
+impl UrlWriter<'_> {
+    /// Writes literal text from the URL template.
+    pub fn text(&mut self, text: &str) {
+        self.query |= text.contains('?');
+        if !self.html {
+            _ = self.out.write_str(text);
+            return;
+        }
+        for ch in text.chars() {
+            _ = match escape_char(ch) {
+                Some(esc) => self.out.write_str(esc),
+                None => self.out.write_char(ch),
+            };
+        }
+    }
+
+    /// Writes a percent-encoded path segment.
+    pub fn segment(&mut self, value: &dyn fmt::Display) {
+        let encode = if self.query {
+            query_needs_encoding
+        } else {
+            segment_needs_encoding
+        };
+        _ = write!(
+            Encoder {
+                out: self.out,
+                encode,
+            },
+            "{value}"
+        );
+    }
+
+    /// Writes a `name=value` query parameter, preceded by `?` or `&`.
+    pub fn param(&mut self, name: &str, value: &dyn fmt::Display) {
+        _ = self.out.write_str(match (self.query, self.html) {
+            (false, _) => "?",
+            (true, false) => "&",
+            (true, true) => "&amp;",
+        });
+        self.query = true;
+        percent_encode_into(self.out, name, query_needs_encoding);
+        _ = self.out.write_char('=');
+        _ = write!(
+            Encoder {
+                out: self.out,
+                encode: query_needs_encoding,
+            },
+            "{value}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::escape::PreEscaped;
+
+    #[test]
+    fn safe_schemes() {
+        for url in [
+            "",
+            "/",
+            "page",
+            "./a:b",
+            "?q=javascript:x",
+            "#javascript:x",
+            "https://example.com",
+            "HTTP://example.com",
+            "mailto:a@example.com",
+            "tel:+123",
+            "data:image/png;base64,AAAA",
+            "DATA:IMAGE/WEBP,AAAA",
+        ] {
+            assert!(is_safe(url), "{url}");
+        }
+    }
+
+    #[test]
+    fn unsafe_schemes() {
+        for url in [
+            "javascript:alert(1)",
+            "JAVASCRIPT:alert(1)",
+            "\u{1}\n javascript:alert(1)",
+            "java\tscript:alert(1)",
+            "jav\r\nascript:alert(1)",
+            "vbscript:msgbox(1)",
+            "data:text/html,<script>",
+            "data:image/svg+xml,<svg>",
+            "data:",
+        ] {
+            assert!(!is_safe(url), "{url:?}");
+        }
+    }
+
+    #[test]
+    fn url_attr() {
+        let render = |url: &str| UrlAttr(String::from(url)).into_string();
+
+        assert_eq!(render("/a b?c=<d>&e"), "/a%20b?c=&lt;d&gt;&amp;e");
+        assert_eq!(render(" https://example.com\n"), "https://example.com");
+        assert_eq!(render("/héllo"), "/h%C3%A9llo");
+        assert_eq!(render("javascript:alert(1)"), INVALID_URL);
+        assert_eq!(
+            UrlAttr(PreEscaped("javascript&colon;alert(1)")).into_string(),
+            INVALID_URL
+        );
+        assert_eq!(
+            (PreEscaped("/"), UrlAttr("java\tscript:")).into_string(),
+            alloc::format!("/{INVALID_URL}")
+        );
+    }
+
+    #[test]
+    fn srcset_attr() {
+        assert_eq!(
+            SrcsetAttr("a.png 1x, b.png 2x").into_string(),
+            "a.png 1x, b.png 2x"
+        );
+        assert_eq!(SrcsetAttr("a.png 1x, javascript:x 2x").into_string(), "");
+    }
+
+    #[test]
+    fn safe_url() {
+        assert_eq!(
+            SafeUrl::new("https://example.com").unwrap().as_str(),
+            "https://example.com"
+        );
+        assert_eq!(SafeUrl::new(" /a\"b ").unwrap().into_string(), "/a%22b");
+        assert_eq!(SafeUrl::new("vbscript:x"), Err(UnsafeUrl));
+    }
+
+    #[test]
+    fn url_builder() {
+        let url = Url::new(|w: &mut UrlWriter| {
+            w.text("/a&b/");
+            w.segment(&"x/y z");
+            w.param("q", &"1&2=3+4");
+            w.param("n", &-1);
+        });
+        assert_eq!(
+            alloc::format!("{url}"),
+            "/a&b/x%2Fy%20z?q=1%262%3D3%2B4&n=-1"
+        );
+        assert_eq!(url.size_hint(), url.into_string().len());
+
+        let url = Url::new(|w: &mut UrlWriter| {
+            w.text("/search?lang=en&q=");
+            w.segment(&"ä/ö&");
+            w.param("page", &2);
+        });
+        assert_eq!(
+            url.into_string(),
+            "/search?lang=en&amp;q=%C3%A4/%C3%B6%26&amp;page=2"
+        );
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("a%20b%2fc").as_deref(), Some("a b/c"));
+        assert_eq!(percent_decode("%C3%A9%").as_deref(), Some("é%"));
+        assert_eq!(percent_decode("%zz%2").as_deref(), Some("%zz%2"));
+        assert_eq!(percent_decode("%FF"), None);
+    }
+
+    #[test]
+    fn escape_policy() {
+        let mut buf = Buffer::new();
+        buf.set_escape_policy(EscapePolicy::Strict);
+        (
+            UrlAttr("/a?b=c"),
+            UrlAttr("javascript:x"),
+            Url::new(|w: &mut UrlWriter| w.param("q", &"'")),
+        )
+            .render_into(&mut buf);
+        assert_eq!(
+            buf.into_string(),
+            alloc::format!("&#x2F;a?b&#x3D;c{INVALID_URL}?q&#x3D;%27")
+        );
+
+        let mut buf = Buffer::new();
+        buf.set_escape_policy(EscapePolicy::Xml);
+        (
+            SrcsetAttr("a\u{1}'.png 1x,\u{FFFE} b.png\u{FFFF} 2x"),
+            PreEscaped(" "),
+            UrlAttr("/a\u{1}b'\u{FFFE}"),
+        )
+            .render_into(&mut buf);
+        assert_eq!(
+            buf.into_string(),
+            "a&#39;.png 1x, b.png 2x /a%01b&#39;%EF%BF%BE"
+        );
+    }
+}
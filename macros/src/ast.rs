@@ -3,12 +3,16 @@
 use proc_macro2::TokenStream;
 use quote::ToTokens;
 use syn::{
+    parenthesized,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
     Error, Expr, ExprMacro, Ident, Lit, LitStr, Macro, Path, Result, Token,
 };
 
-use crate::known::{is_known_tag, is_void_tag};
+use crate::{
+    known::{is_known_tag, is_void_tag},
+    kw,
+};
 
 /// A single ident `foo`, or a string literal `"bar"`.
 pub enum AttrName {
@@ -124,8 +128,37 @@ impl ToTokens for Attr {
     }
 }
 
+/// A named slot passed to a component, `slot name = value`.
+pub struct Slot {
+    pub slot_token: kw::slot,
+    pub name: Ident,
+    pub eq_token: Token![=],
+    pub value: Node,
+}
+
+impl Parse for Slot {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Self {
+            slot_token: input.parse()?,
+            name: input.parse()?,
+            eq_token: input.parse()?,
+            value: input.parse()?,
+        })
+    }
+}
+
+impl ToTokens for Slot {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.slot_token.to_tokens(tokens);
+        self.name.to_tokens(tokens);
+        self.eq_token.to_tokens(tokens);
+        self.value.to_tokens(tokens);
+    }
+}
+
 pub struct ElementBody {
     pub attrs: Vec<Attr>,
+    pub slots: Vec<Slot>,
     pub nodes: Vec<Node>,
 }
 
@@ -137,6 +170,7 @@ impl Parse for ElementBody {
                 .peekable();
 
         let mut attrs = Vec::new();
+        let mut slots = Vec::<Slot>::new();
         let mut nodes = Vec::new();
 
         while let Some(AttrOrNode::Attr(_)) = parts.peek() {
@@ -154,11 +188,24 @@ impl Parse for ElementBody {
                         "attributes must be at the beginning",
                     ))
                 }
+                AttrOrNode::Slot(slot) => {
+                    if slots.iter().any(|s| s.name == slot.name) {
+                        return Err(Error::new_spanned(
+                            slot.name,
+                            "duplicate slot",
+                        ));
+                    }
+                    slots.push(slot);
+                }
                 AttrOrNode::Node(node) => nodes.push(node),
             };
         }
 
-        Ok(Self { attrs, nodes })
+        Ok(Self {
+            attrs,
+            slots,
+            nodes,
+        })
     }
 }
 
@@ -167,6 +214,9 @@ impl ToTokens for ElementBody {
         for attr in &self.attrs {
             attr.to_tokens(tokens);
         }
+        for slot in &self.slots {
+            slot.to_tokens(tokens);
+        }
         for node in &self.nodes {
             node.to_tokens(tokens);
         }
@@ -193,6 +243,13 @@ impl Element {
             return Err(Error::new_spanned(name, "unknown tag name"));
         }
 
+        if let Some(slot) = body.slots.first() {
+            return Err(Error::new_spanned(
+                slot,
+                "slots can only be passed to components",
+            ));
+        }
+
         if is_void_tag(&name) && !body.nodes.is_empty() {
             return Err(Error::new_spanned(
                 body.nodes.first().unwrap(),
@@ -226,13 +283,161 @@ impl ToTokens for Element {
     }
 }
 
+/// The input of the `fragment!` macro: the name of the fragment, followed by
+/// its content.
+pub struct FragmentInput {
+    pub name: Expr,
+    pub nodes: Vec<Node>,
+}
+
+impl Parse for FragmentInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        if input.is_empty() {
+            return Ok(Self {
+                name,
+                nodes: Vec::new(),
+            });
+        }
+        input.parse::<Token![,]>()?;
+
+        let body = input.parse::<ElementBody>()?;
+        if let Some(attr) = body.attrs.first() {
+            return Err(Error::new_spanned(
+                attr,
+                "fragments cannot have attributes",
+            ));
+        }
+        if let Some(slot) = body.slots.first() {
+            return Err(Error::new_spanned(
+                slot,
+                "slots can only be passed to components",
+            ));
+        }
+
+        Ok(Self {
+            name,
+            nodes: body.nodes,
+        })
+    }
+}
+
+/// The input of the `csp_nonce!` macro: the names of the elements that get a
+/// nonce, if not the default ones, followed by an element.
+///
+/// ```text
+/// csp_nonce := ['[' [identifier],* ']' ','] node
+/// ```
+pub struct NonceInput {
+    pub elements: Vec<String>,
+    pub node: Node,
+}
+
+impl Parse for NonceInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let elements = if input.peek(syn::token::Bracket) {
+            let content;
+            syn::bracketed!(content in input);
+            let names =
+                Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+            input.parse::<Token![,]>()?;
+            let mut elements = Vec::new();
+            for name in names {
+                if !is_known_tag(&name.to_string()) {
+                    return Err(Error::new_spanned(name, "unknown tag name"));
+                }
+                elements.push(name.to_string());
+            }
+            elements
+        } else {
+            ["script", "style", "link"].map(String::from).to_vec()
+        };
+
+        Ok(Self {
+            elements,
+            node: input.parse()?,
+        })
+    }
+}
+
+/// A component called with slots, `Component(slot name = value, children)`.
+pub struct Component {
+    pub path: Path,
+    pub body: ElementBody,
+}
+
+impl Component {
+    /// Returns `true` if `input` starts with a call whose arguments include a
+    /// slot, which would not parse as an expression.
+    fn peek(input: ParseStream) -> bool {
+        let fork = input.fork();
+        let Ok(content) = fork.call(Path::parse_mod_style).and_then(|_| {
+            let content;
+            parenthesized!(content in fork);
+            Ok(content)
+        }) else {
+            return false;
+        };
+        if !fork.is_empty() && !fork.peek(Token![,]) {
+            return false;
+        }
+
+        while !content.is_empty() {
+            if content.peek(kw::slot)
+                && content.peek2(Ident)
+                && content.peek3(Token![=])
+            {
+                return true;
+            }
+            // Skip to the next argument.
+            while !content.is_empty() && !content.peek(Token![,]) {
+                if content.parse::<proc_macro2::TokenTree>().is_err() {
+                    return false;
+                }
+            }
+            _ = content.parse::<Option<Token![,]>>();
+        }
+        false
+    }
+}
+
+impl Parse for Component {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let path = input.call(Path::parse_mod_style)?;
+        let content;
+        parenthesized!(content in input);
+        let body: ElementBody = content.parse()?;
+        if let Some(attr) = body.attrs.first() {
+            return Err(Error::new_spanned(
+                attr,
+                "components take slots instead of attributes, e.g. `slot name \
+                 = value`",
+            ));
+        }
+
+        Ok(Self { path, body })
+    }
+}
+
+impl ToTokens for Component {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.path.to_tokens(tokens);
+        self.body.to_tokens(tokens);
+    }
+}
+
 pub enum Node {
     Element(Element),
+    Component(Component),
     Expr(Expr),
 }
 
 impl Parse for Node {
     fn parse(input: ParseStream) -> Result<Self> {
+        if Component::peek(input) {
+            return Ok(Self::Component(input.parse()?));
+        }
+
         let expr = input.parse()?;
         if let Expr::Macro(ExprMacro { mac, .. }) = &expr {
             if let Ok(el) = Element::from_macro(mac.clone()) {
@@ -248,6 +453,7 @@ impl ToTokens for Node {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match self {
             Node::Element(element) => element.to_tokens(tokens),
+            Node::Component(component) => component.to_tokens(tokens),
             Node::Expr(expr) => expr.to_tokens(tokens),
         }
     }
@@ -255,6 +461,7 @@ impl ToTokens for Node {
 
 enum AttrOrNode {
     Attr(Attr),
+    Slot(Slot),
     Node(Node),
 }
 
@@ -265,6 +472,11 @@ impl Parse for AttrOrNode {
                 || (input.peek2(Token![?]) && input.peek3(Token![=])))
         {
             Ok(Self::Attr(input.parse()?))
+        } else if input.peek(kw::slot)
+            && input.peek2(Ident)
+            && input.peek3(Token![=])
+        {
+            Ok(Self::Slot(input.parse()?))
         } else {
             Ok(Self::Node(input.parse()?))
         }
@@ -0,0 +1,87 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use sha2::{Digest, Sha256};
+use syn::{Error, Expr, ExprLit, ExprMacro, Lit, Result};
+use vy_core::Buffer;
+
+use crate::{ast::Node, fmt::Serializer};
+
+/// Expands to the CSP hash source (`'sha256-...'`) of a static inline script
+/// or style.
+pub fn hash(node: Node) -> Result<TokenStream> {
+    let (node, minify) = match node {
+        Node::Expr(Expr::Macro(ExprMacro { mac, .. }))
+            if mac.path.is_ident("minify") =>
+        {
+            (mac.parse_body()?, true)
+        }
+        node => (node, false),
+    };
+
+    let content = match node {
+        Node::Element(el) => {
+            let name = el.0.name.to_string();
+            if name != "script" && name != "style" {
+                return Err(Error::new_spanned(
+                    el.0.name,
+                    "expected a `script!` or `style!` element",
+                ));
+            }
+
+            let mut buf = Buffer::new();
+            let mut ser = if minify {
+                Serializer::minified(&mut buf)
+            } else {
+                Serializer::new(&mut buf)
+            };
+            ser.write_content(&name, el.1.nodes);
+            if let Some(expr) = ser.first_value() {
+                return Err(Error::new_spanned(
+                    expr,
+                    "only static content can be hashed",
+                ));
+            }
+            drop(ser);
+            buf.into_string()
+        }
+        Node::Expr(Expr::Lit(ExprLit {
+            lit: Lit::Str(lit_str),
+            ..
+        })) => lit_str.value(),
+        node => {
+            return Err(Error::new_spanned(
+                node,
+                "expected a string literal or a `script!` or `style!` element",
+            ))
+        }
+    };
+
+    let digest = Sha256::digest(content.as_bytes());
+    let source = format!("'sha256-{}'", base64(&digest));
+
+    Ok(quote!(#source))
+}
+
+/// Encodes `input` using the standard base64 alphabet with padding.
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
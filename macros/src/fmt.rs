@@ -1,19 +1,60 @@
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, ToTokens};
 use syn::{
-    parse_quote, Block, Expr, ExprBlock, ExprGroup, ExprLit, Ident, Lit, Token,
+    parse_quote, spanned::Spanned, Block, Expr, ExprBlock, ExprGroup, ExprLit,
+    ExprMacro, Ident, Lit, Stmt, Token,
 };
 use vy_core::{Buffer, IntoHtml};
 
 use crate::{
-    ast::{Attr, AttrValue, Element, Node},
-    known::is_void_tag,
+    ast::{Attr, AttrValue, Component, Element, Node, Slot},
+    known::{is_phrasing_tag, is_void_tag},
+    minify,
 };
 
+/// The kind of content that static text is written into.
+#[derive(Clone, Copy, PartialEq)]
+enum Content {
+    Text,
+    /// The content of `pre` and `textarea`, where whitespace is significant.
+    Preformatted,
+    Script,
+    Style,
+}
+
+/// A structural mark for pretty-printed output, see `vy::pretty::Mark`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mark {
+    Open,
+    Close,
+    Void,
+    Verbatim,
+    EndVerbatim,
+}
+
+impl Mark {
+    /// Returns the tokens of this mark at `offset`.
+    pub fn to_tokens(self, offset: TokenStream) -> TokenStream {
+        let variant = match self {
+            Self::Open => quote!(Open),
+            Self::Close => quote!(Close),
+            Self::Void => quote!(Void),
+            Self::Verbatim => quote!(Verbatim),
+            Self::EndVerbatim => quote!(EndVerbatim),
+        };
+        quote!(::vy::pretty::Mark::#variant(#offset))
+    }
+}
+
 pub struct Serializer<'s> {
     buf: &'s mut Buffer,
     values: Vec<(usize, Expr)>,
+    marks: Vec<(usize, Mark)>,
     imports: Vec<Ident>,
+    minify: bool,
+    /// The names of the elements that get a CSP nonce.
+    nonces: Option<Vec<String>>,
+    content: Content,
 }
 
 impl<'s> Serializer<'s> {
@@ -21,31 +62,38 @@ impl<'s> Serializer<'s> {
         Self {
             buf,
             values: Vec::new(),
+            marks: Vec::new(),
             imports: Vec::new(),
+            minify: false,
+            nonces: None,
+            content: Content::Text,
         }
     }
 
-    pub fn write_expr(&mut self, mut expr: Expr) {
+    /// Creates a serializer that minifies static content.
+    pub fn minified(buf: &'s mut Buffer) -> Self {
+        Self {
+            minify: true,
+            ..Self::new(buf)
+        }
+    }
+
+    /// Adds a `nonce` attribute to the `elements`, which is rendered if a
+    /// CSP nonce is provided in the render context.
+    pub fn with_nonces(self, elements: Vec<String>) -> Self {
+        Self {
+            nonces: Some(elements),
+            ..self
+        }
+    }
+
+    pub fn write_expr(&mut self, expr: Expr) {
         match expr {
             Expr::Group(ExprGroup { attrs, expr, .. }) if attrs.is_empty() => {
                 self.write_expr(*expr);
             }
             Expr::If(_) => {
-                let mut count = 1;
-                let mut current = Some(&expr);
-                while let Some(Expr::If(node)) = current {
-                    current =
-                        node.else_branch.as_ref().map(|(_, expr)| &**expr);
-                    count += 1;
-                }
-
-                let either_suffix =
-                    if count > 2 { Some(count) } else { None }.into_string();
-                let either = format_ident!("Either{either_suffix}");
-
-                transform_branches(&either, 0, &mut expr);
-
-                self.values.push((self.buf.len(), expr));
+                self.values.push((self.buf.len(), into_either(expr)));
             }
             Expr::Lit(ExprLit {
                 attrs,
@@ -85,43 +133,87 @@ impl<'s> Serializer<'s> {
 
     pub fn write_attr(&mut self, attr: Attr) {
         let name = attr.name.to_string();
+        let url_wrapper = url_wrapper(&name);
         if attr.is_optional() {
             let sep_name = String::from(' ') + &name;
             let sep_name_eq = sep_name.clone() + "=\"";
+            let val = match url_wrapper {
+                Some(wrapper) => quote!(#wrapper(val)),
+                None => quote!(val),
+            };
 
             match attr.value {
                 AttrValue::Expr(value) => self.write_expr(parse_quote! {
                     ::core::option::Option::map(
                         #value,
-                        |val| (::vy::PreEscaped(#sep_name_eq), val, vy::PreEscaped('"'))
+                        |val| (::vy::PreEscaped(#sep_name_eq), #val, vy::PreEscaped('"'))
                     )
                 }),
-                AttrValue::Bool(value) => self.write_expr(parse_quote!{
-                    <bool>::then_some(#value, ::vy::PreEscaped(#sep_name))
-                }),
+                AttrValue::Bool(value) => {
+                    if value {
+                        self.buf.push_str(&sep_name);
+                    }
+                }
             }
         } else {
             self.buf.push(' ');
             self.buf.push_str(&name);
             self.buf.push('=');
+            if self.minify {
+                if let Some(value) = static_value(&attr.value) {
+                    let mut escaped = Buffer::new();
+                    value.escape_and_write(&mut escaped);
+                    if minify::is_unquoted_safe(escaped.as_str()) {
+                        self.buf.push_str(escaped.as_str());
+                        return;
+                    }
+                }
+            }
             self.buf.push('"');
-            self.write_expr(attr.value.into());
+            match (url_wrapper, Expr::from(attr.value)) {
+                (Some(wrapper), value) if !is_lit(&value) => {
+                    let value = into_either(value);
+                    self.write_expr(parse_quote!(#wrapper(#value)));
+                }
+                (_, value) => self.write_expr(value),
+            }
             self.buf.push('"');
         }
     }
 
     pub fn write_element(&mut self, Element(head, body): Element) {
         let name = head.name.to_string();
+        let nonce = self
+            .nonces
+            .as_ref()
+            .is_some_and(|elements| needs_nonce(elements, &name, &body.attrs));
+        let void = is_void_tag(&name);
+        let block = !is_phrasing_tag(&name);
+        let verbatim =
+            matches!(&*name, "pre" | "script" | "style" | "textarea");
         self.imports.push(head.name);
+        if block {
+            self.mark(if void { Mark::Void } else { Mark::Open });
+        }
         self.buf.push('<');
         self.buf.push_str(&name);
         for attr in body.attrs {
             self.write_attr(attr);
         }
+        if nonce {
+            self.write_expr(parse_quote!(::vy::csp::NonceAttr));
+        }
         self.buf.push('>');
-        if !is_void_tag(&name) {
-            for node in body.nodes {
-                self.write_node(node);
+        if !void {
+            if verbatim {
+                self.mark(Mark::Verbatim);
+            }
+            self.write_content(&name, body.nodes);
+            if verbatim {
+                self.mark(Mark::EndVerbatim);
+            }
+            if block {
+                self.mark(Mark::Close);
             }
             self.buf.push('<');
             self.buf.push('/');
@@ -130,11 +222,138 @@ impl<'s> Serializer<'s> {
         }
     }
 
+    /// Records a structural mark at the current offset.
+    fn mark(&mut self, mark: Mark) {
+        self.marks.push((self.buf.len(), mark));
+    }
+
+    /// Writes the content of a `name` element.
+    pub fn write_content(&mut self, name: &str, nodes: Vec<Node>) {
+        let content = self.content;
+        self.content = match name {
+            "pre" | "textarea" => Content::Preformatted,
+            "script" => Content::Script,
+            "style" => Content::Style,
+            _ => content,
+        };
+        match self.minify_source(&nodes) {
+            Some(source) => source.escape_and_write(self.buf),
+            None => {
+                for node in nodes {
+                    self.write_node(node);
+                }
+            }
+        }
+        self.content = content;
+    }
+
     pub fn write_node(&mut self, node: Node) {
         match node {
             Node::Element(el) => self.write_element(el),
-            Node::Expr(expr) => self.write_expr(expr),
+            Node::Component(component) => {
+                let expr = self.expand_component(component);
+                self.write_expr(expr);
+            }
+            Node::Expr(expr) => match lit_str(&expr) {
+                Some(text) if self.minify && self.content == Content::Text => {
+                    let mut text = minify::collapse_whitespace(&text);
+                    // Merge with the whitespace of a preceding literal.
+                    if self.buf.as_str().ends_with(' ')
+                        && self.values.last().map(|(i, _)| *i)
+                            != Some(self.buf.len())
+                        && text.starts_with(' ')
+                    {
+                        text.remove(0);
+                    }
+                    text.escape_and_write(self.buf);
+                }
+                _ => self.write_expr(expr),
+            },
+        }
+    }
+
+    /// Expands a component into a call of its constructor, followed by a call
+    /// of the method of each slot that is filled. The children fill the
+    /// `children` slot.
+    fn expand_component(&mut self, component: Component) -> Expr {
+        let Component { path, body } = component;
+        let slots: Vec<_> = body
+            .slots
+            .into_iter()
+            .map(|Slot { name, value, .. }| {
+                let value = self.expand_nodes(vec![value]);
+                quote!(.#name(#value))
+            })
+            .collect();
+        let children = body.nodes.first().map(Spanned::span).map(|span| {
+            let method = Ident::new("children", span);
+            let value = self.expand_nodes(body.nodes);
+            quote!(.#method(#value))
+        });
+
+        parse_quote!(#path::new() #(#slots)* #children)
+    }
+
+    /// Expands `nodes` into a standalone expression.
+    fn expand_nodes(&mut self, mut nodes: Vec<Node>) -> Expr {
+        if let [Node::Expr(_)] = &nodes[..] {
+            let Some(Node::Expr(expr)) = nodes.pop() else {
+                unreachable!();
+            };
+            return expr;
+        }
+
+        let mut buf = Buffer::new();
+        let mut ser = Serializer {
+            buf: &mut buf,
+            values: Vec::new(),
+            marks: Vec::new(),
+            imports: Vec::new(),
+            minify: self.minify,
+            nonces: self.nonces.clone(),
+            content: self.content,
+        };
+        for node in nodes {
+            ser.write_node(node);
         }
+        self.imports.append(&mut ser.imports);
+        let html = crate::expand_parts(ser.into_parts());
+        parse_quote!(#html)
+    }
+
+    /// Returns the minified content of a script or style made only of string
+    /// literals.
+    fn minify_source(&self, nodes: &[Node]) -> Option<String> {
+        if !self.minify
+            || !matches!(self.content, Content::Script | Content::Style)
+        {
+            return None;
+        }
+        let mut source = String::new();
+        for node in nodes {
+            match node {
+                Node::Expr(expr) => source.push_str(&lit_str(expr)?),
+                Node::Element(_) | Node::Component(_) => return None,
+            }
+        }
+        Some(match self.content {
+            Content::Script => minify::js(&source),
+            _ => minify::css(&source),
+        })
+    }
+
+    /// Returns the first expression, if any.
+    pub fn first_value(&self) -> Option<&Expr> {
+        self.values.first().map(|(_, expr)| expr)
+    }
+
+    /// Returns the first expression that is not a `static_part!` call, if
+    /// any.
+    pub fn first_dynamic_value(&self) -> Option<&Expr> {
+        self.values
+            .iter()
+            .map(|(_, expr)| expr)
+            .find(|expr| static_part(expr).is_none())
     }
 
     pub fn as_imports(&self) -> TokenStream {
@@ -149,28 +368,175 @@ impl<'s> Serializer<'s> {
     pub fn into_parts(self) -> Vec<Part<'s>> {
         let mut parts = Vec::new();
         let mut cursor = 0;
+        let html = self.buf.as_str();
+        let marks = &self.marks;
+        // The marks in `html[start..end]`, relative to `start`. A verbatim
+        // mark follows a start tag, the others precede a tag.
+        let str_part = |start: usize, end: usize| {
+            let marks = marks
+                .iter()
+                .filter(|&&(at, mark)| {
+                    if mark == Mark::Verbatim {
+                        start < at && at <= end
+                    } else {
+                        start <= at && (at < end || end == html.len())
+                    }
+                })
+                .map(|&(at, mark)| (at - start, mark))
+                .collect();
+            Part::Str(&html[start..end], marks)
+        };
 
         for (i, val) in self.values {
             assert!(i >= cursor);
-            let slice = &self.buf.as_str()[cursor..i];
-            if !slice.is_empty() {
-                parts.push(Part::Str(slice));
+            if cursor < i {
+                parts.push(str_part(cursor, i));
             }
-            parts.push(Part::Expr(val));
+            parts.push(match static_part(&val) {
+                Some(expr) => Part::Const(expr),
+                None => Part::Expr(val),
+            });
             cursor = i;
         }
 
-        let slice = &self.buf.as_str()[cursor..];
-        if !slice.is_empty() {
-            parts.push(Part::Str(slice));
+        if cursor < html.len() {
+            parts.push(str_part(cursor, html.len()));
         }
 
         parts
     }
 }
 
+/// Returns the expression passed to a `static_part!` call, if `expr` is one.
+///
+/// If it does not parse, the call is expanded as is and reports the error.
+fn static_part(expr: &Expr) -> Option<Expr> {
+    match expr {
+        Expr::Macro(ExprMacro { attrs, mac, .. })
+            if attrs.is_empty() && mac.path.is_ident("static_part") =>
+        {
+            mac.parse_body().ok()
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether a CSP nonce should be injected into the element.
+///
+/// This is the case for the given `elements`, unless a nonce is already
+/// given. `link` elements only get one if they load styles or scripts, which
+/// requires a literal `rel` attribute.
+fn needs_nonce(elements: &[String], name: &str, attrs: &[Attr]) -> bool {
+    let attr = |name: &str| attrs.iter().find(|a| a.name.to_string() == name);
+
+    if attr("nonce").is_some() || !elements.iter().any(|el| el == name) {
+        return false;
+    }
+    if name != "link" {
+        return true;
+    }
+    match attr("rel").map(|a| &a.value) {
+        Some(AttrValue::Expr(Expr::Lit(ExprLit {
+            lit: Lit::Str(rel),
+            ..
+        }))) => rel.value().split_ascii_whitespace().any(|rel| {
+            rel.eq_ignore_ascii_case("stylesheet")
+                || rel.eq_ignore_ascii_case("preload")
+                || rel.eq_ignore_ascii_case("modulepreload")
+        }),
+        _ => false,
+    }
+}
+
+/// Returns the wrapper that checks the rendered value of a URL attribute.
+fn url_wrapper(name: &str) -> Option<TokenStream> {
+    match name.to_ascii_lowercase().as_str() {
+        "action" | "background" | "cite" | "formaction" | "href"
+        | "longdesc" | "manifest" | "poster" | "src" | "xlink:href" => {
+            Some(quote!(::vy::url::UrlAttr))
+        }
+        "imagesrcset" | "srcset" => Some(quote!(::vy::url::SrcsetAttr)),
+        _ => None,
+    }
+}
+
+/// Returns whether `expr` is a literal, possibly in invisible groups.
+fn is_lit(expr: &Expr) -> bool {
+    match expr {
+        Expr::Group(ExprGroup { attrs, expr, .. }) if attrs.is_empty() => {
+            is_lit(expr)
+        }
+        Expr::Lit(ExprLit { attrs, .. }) => attrs.is_empty(),
+        _ => false,
+    }
+}
+
+/// Strips invisible groups from `expr`.
+fn ungroup(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Group(ExprGroup { attrs, expr, .. }) if attrs.is_empty() => {
+            ungroup(expr)
+        }
+        _ => expr,
+    }
+}
+
+/// Returns the value of a string literal, possibly in invisible groups.
+fn lit_str(expr: &Expr) -> Option<String> {
+    match ungroup(expr) {
+        Expr::Lit(ExprLit {
+            attrs,
+            lit: Lit::Str(lit_str),
+        }) if attrs.is_empty() => Some(lit_str.value()),
+        _ => None,
+    }
+}
+
+/// Returns the rendered value of a static attribute value.
+fn static_value(value: &AttrValue) -> Option<String> {
+    let expr = match value {
+        AttrValue::Bool(b) => return Some(b.to_string()),
+        AttrValue::Expr(expr) => ungroup(expr),
+    };
+    match expr {
+        Expr::Lit(ExprLit { attrs, lit }) if attrs.is_empty() => match lit {
+            Lit::Str(lit) => Some(lit.value()),
+            Lit::Char(lit) => Some(lit.value().to_string()),
+            Lit::Int(lit) => Some(lit.base10_digits().to_owned()),
+            Lit::Float(lit) => Some(lit.base10_digits().to_owned()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Wraps the branches of an `if` expression in the matching `Either` type,
+/// so that they render as a single type.
+fn into_either(mut expr: Expr) -> Expr {
+    let mut count = 1;
+    let mut current = Some(&expr);
+    while let Some(Expr::If(node)) = current {
+        current = node.else_branch.as_ref().map(|(_, expr)| &**expr);
+        count += 1;
+    }
+    if count == 1 {
+        return expr;
+    }
+
+    let either_suffix =
+        if count > 2 { Some(count) } else { None }.into_string();
+    let either = format_ident!("Either{either_suffix}");
+
+    transform_branches(&either, 0, &mut expr);
+    expr
+}
+
 pub enum Part<'s> {
-    Str(&'s str),
+    /// Static HTML, with its structural marks at their offsets.
+    Str(&'s str, Vec<(usize, Mark)>),
+    /// The expression of a `static_part!` call, evaluating to static HTML
+    /// which is concatenated with the surrounding strings at compile time.
+    Const(Expr),
     Expr(Expr),
 }
 
@@ -192,15 +558,24 @@ fn wrap_branch(either: &Ident, count: u8, tokens: impl ToTokens) -> Block {
     })
 }
 
+/// Like [`wrap_branch`], but unwraps blocks containing a single expression
+/// to avoid unnecessary braces.
+fn wrap_block(either: &Ident, count: u8, block: &Block) -> Block {
+    match block.stmts.as_slice() {
+        [Stmt::Expr(expr, None)] => wrap_branch(either, count, expr),
+        _ => wrap_branch(either, count, block),
+    }
+}
+
 fn transform_branches(either: &Ident, mut count: u8, expr: &mut Expr) {
     count += 1;
     match expr {
         Expr::Block(ExprBlock { block, .. }) => {
-            *block = wrap_branch(either, count, &block);
+            *block = wrap_block(either, count, block);
         }
         Expr::If(expr_if) => {
             expr_if.then_branch =
-                wrap_branch(either, count, &expr_if.then_branch);
+                wrap_block(either, count, &expr_if.then_branch);
 
             if let Some((_, else_branch)) = &mut expr_if.else_branch {
                 transform_branches(either, count, else_branch);
@@ -243,6 +243,19 @@ pub fn is_void_tag(name: &str) -> bool {
     VOID_TAGS.binary_search(&name).is_ok()
 }
 
+/// Returns whether `name` is a phrasing element, which is kept inline with
+/// the surrounding text in pretty-printed output.
+pub fn is_phrasing_tag(name: &str) -> bool {
+    const PHRASING_TAGS: &[&str] = &[
+        "a", "abbr", "b", "bdi", "bdo", "br", "button", "cite", "code", "data",
+        "del", "dfn", "em", "i", "img", "input", "ins", "kbd", "label", "mark",
+        "meter", "output", "picture", "progress", "q", "s", "samp", "select",
+        "small", "span", "strong", "sub", "sup", "svg", "textarea", "time",
+        "u", "var", "wbr",
+    ];
+    PHRASING_TAGS.binary_search(&name).is_ok()
+}
+
 pub fn is_known_tag(name: &str) -> bool {
     macro_rules! define_tag_names {
         ($($(#[doc=$doc:literal])* $el:ident)+) => {
@@ -0,0 +1,323 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    braced,
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    parse_quote, Attribute, Error, Expr, FnArg, Ident, ItemFn, Pat, PatIdent,
+    PatType, Result, Stmt, Token, Type, TypeImplTrait, TypeParamBound,
+    Visibility,
+};
+
+/// The input of the `layout!` macro: a struct declaring the slots of the
+/// layout with their default content, followed by the HTML of the layout.
+///
+/// ```text
+/// layout := [attribute]* visibility 'struct' identifier '{' [slot],* '}'
+///           '=>' expression [';']
+/// slot := [attribute]* identifier '=' expression
+/// ```
+pub struct Layout {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    slots: Vec<Slot>,
+    /// The statements rendering the layout, ending with its HTML.
+    body: Vec<Stmt>,
+}
+
+struct Slot {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    default: Expr,
+}
+
+impl Parse for Layout {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+
+        let content;
+        braced!(content in input);
+        let mut slots = Vec::<Slot>::new();
+        while !content.is_empty() {
+            let attrs = content.call(Attribute::parse_outer)?;
+            let name = content.parse::<Ident>()?;
+            check_slot_name(&slots, &name)?;
+            content.parse::<Token![=]>()?;
+            let default = content.parse()?;
+            slots.push(Slot {
+                attrs,
+                name,
+                default,
+            });
+            if content.parse::<Option<Token![,]>>()?.is_none() {
+                break;
+            }
+        }
+        if !content.is_empty() {
+            return Err(content.error("expected `,`"));
+        }
+
+        input.parse::<Token![=>]>()?;
+        let body = Stmt::Expr(input.parse()?, None);
+        input.parse::<Option<Token![;]>>()?;
+
+        Ok(Self {
+            attrs,
+            vis,
+            name,
+            slots,
+            body: vec![body],
+        })
+    }
+}
+
+/// Turns a component function into a layout, with a slot for each parameter
+/// and the body of the function as its HTML.
+///
+/// The parameters must take `impl IntoHtml`, since a slot takes any value
+/// that implements it. The default content of a slot is given by a
+/// `#[slot(default = ...)]` attribute on the parameter, and is empty
+/// otherwise.
+pub fn component(item: ItemFn) -> Result<TokenStream> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+    if let Some(param) = sig.generics.params.first() {
+        return Err(Error::new_spanned(param, "components cannot be generic"));
+    }
+    if let Some(token) = sig.asyncness {
+        return Err(Error::new_spanned(token, "components cannot be async"));
+    }
+
+    let mut slots = Vec::new();
+    for input in sig.inputs {
+        let PatType { attrs, pat, ty, .. } = match input {
+            FnArg::Typed(pat_type) => pat_type,
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new_spanned(
+                    receiver,
+                    "components cannot take `self`",
+                ))
+            }
+        };
+        let name = match *pat {
+            Pat::Ident(PatIdent {
+                by_ref: None,
+                mutability: None,
+                ident,
+                subpat: None,
+                ..
+            }) => ident,
+            pat => return Err(Error::new_spanned(pat, "expected a slot name")),
+        };
+        check_slot_name(&slots, &name)?;
+        if !is_impl_into_html(&ty) {
+            return Err(Error::new_spanned(
+                ty,
+                "slots take `impl IntoHtml`, since they accept any HTML",
+            ));
+        }
+
+        let mut default = None;
+        let mut slot_attrs = Vec::new();
+        for attr in attrs {
+            if attr.path().is_ident("slot") {
+                default =
+                    Some(attr.parse_args_with(|input: ParseStream| {
+                        let key = input.parse::<Ident>()?;
+                        if key != "default" {
+                            return Err(Error::new_spanned(
+                                key,
+                                "expected `default`",
+                            ));
+                        }
+                        input.parse::<Token![=]>()?;
+                        input.parse::<Expr>()
+                    })?);
+            } else {
+                slot_attrs.push(attr);
+            }
+        }
+        slots.push(Slot {
+            attrs: slot_attrs,
+            name,
+            default: default.unwrap_or_else(|| parse_quote!(())),
+        });
+    }
+
+    Ok(expand(Layout {
+        attrs,
+        vis,
+        name: sig.ident,
+        slots,
+        body: block.stmts,
+    }))
+}
+
+/// Returns `true` if `ty` is `impl IntoHtml`, with any path to the trait.
+fn is_impl_into_html(ty: &Type) -> bool {
+    let Type::ImplTrait(TypeImplTrait { bounds, .. }) = ty else {
+        return false;
+    };
+    let mut bounds = bounds.iter();
+    match (bounds.next(), bounds.next()) {
+        (Some(TypeParamBound::Trait(bound)), None) => {
+            bound.path.segments.last().is_some_and(|last| {
+                last.ident == "IntoHtml" && last.arguments.is_none()
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Returns an error if `name` cannot be the name of another slot.
+fn check_slot_name(slots: &[Slot], name: &Ident) -> Result<()> {
+    if name == "new" {
+        return Err(Error::new_spanned(
+            name,
+            "`new` is reserved for the constructor of the layout",
+        ));
+    }
+    if slots.iter().any(|slot| slot.name.unraw() == name.unraw()) {
+        return Err(Error::new_spanned(name, "duplicate slot"));
+    }
+    Ok(())
+}
+
+/// Expands to the struct of the layout, with a method filling each slot and
+/// an `IntoHtml` implementation.
+pub fn expand(layout: Layout) -> TokenStream {
+    let Layout {
+        attrs,
+        vis,
+        name,
+        slots,
+        body,
+    } = layout;
+
+    let fields: Vec<_> = slots.iter().map(|slot| &slot.name).collect();
+    let params: Vec<_> = fields.iter().map(|field| type_param(field)).collect();
+    let defaults = slots.iter().map(|slot| &slot.default);
+
+    let setters = slots.iter().enumerate().map(|(i, slot)| {
+        let Slot {
+            attrs, name: slot, ..
+        } = slot;
+        let doc = format!("Fills the `{}` slot.", slot.unraw());
+        let output = params.iter().enumerate().map(|(j, param)| {
+            if i == j {
+                quote!(__Slot)
+            } else {
+                quote!(#param)
+            }
+        });
+        let others = fields.iter().filter(|field| **field != slot);
+        quote! {
+            #[doc = #doc]
+            #(#attrs)*
+            #[inline]
+            #vis fn #slot<__Slot: ::vy::IntoHtml>(
+                self,
+                #slot: __Slot,
+            ) -> #name<#(#output),*> {
+                #name {
+                    #slot,
+                    #(#others: self.#others,)*
+                }
+            }
+        }
+    });
+
+    let slots = quote! {
+        let #name { #(#fields),* } = self;
+        #(
+            let #fields = ::vy::layout::Slot::fill(#fields, || #defaults);
+        )*
+    };
+    let render = quote! {
+        #[inline]
+        fn into_html(self) -> impl ::vy::IntoHtml {
+            #slots
+            #(#body)*
+        }
+    };
+
+    // Components are called like functions with their children alone, as in
+    // `Card(p!(..))`. The struct has named fields, so the function does not
+    // clash with it.
+    let call = fields
+        .iter()
+        .position(|field| *field == "children")
+        .map(|i| {
+            let output = params.iter().enumerate().map(|(j, _)| {
+                if i == j {
+                    quote!(__Slot)
+                } else {
+                    quote!(::vy::layout::Unset)
+                }
+            });
+            quote! {
+                /// Creates the layout with `children` in the `children` slot.
+                #[allow(non_snake_case)]
+                #[inline]
+                #vis fn #name<__Slot: ::vy::IntoHtml>(
+                    children: __Slot,
+                ) -> #name<#(#output),*> {
+                    #name::new().children(children)
+                }
+            }
+        });
+
+    quote! {
+        #(#attrs)*
+        #[allow(non_camel_case_types)]
+        #vis struct #name<#(#params = ::vy::layout::Unset),*> {
+            #(#fields: #params,)*
+        }
+
+        impl #name {
+            /// Creates the layout with the default content in every slot.
+            #[inline]
+            #vis const fn new() -> Self {
+                Self {
+                    #(#fields: ::vy::layout::Unset,)*
+                }
+            }
+        }
+
+        #call
+
+        impl ::core::default::Default for #name {
+            #[inline]
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        #[allow(non_camel_case_types)]
+        impl<#(#params),*> #name<#(#params),*> {
+            #(#setters)*
+        }
+
+        #[allow(non_camel_case_types)]
+        impl<#(#params: ::vy::layout::Slot),*> ::vy::IntoHtml
+            for #name<#(#params),*>
+        {
+            #render
+        }
+    }
+}
+
+/// Returns the name of the type parameter of a slot, such as
+/// `__VySlot_head_extra` for `head_extra`, which cannot shadow the types used
+/// by the layout.
+fn type_param(slot: &Ident) -> Ident {
+    format_ident!("__VySlot_{}", slot.unraw(), span = slot.span())
+}
@@ -1,20 +1,28 @@
 mod ast;
+mod csp;
 mod fmt;
 #[macro_use]
 mod known;
+mod layout;
+mod minify;
+mod route;
+mod url;
 
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{parse::Parse, parse_macro_input};
+use syn::{
+    parse::Parse, parse_macro_input, DeriveInput, Expr, ExprMacro, ItemFn,
+};
 use vy_core::Buffer;
 
 use self::{
-    ast::{Element, ElementBody, ElementHead},
+    ast::{Element, ElementBody, ElementHead, FragmentInput, Node, NonceInput},
     fmt::{Part, Serializer},
 };
 
 mod kw {
     syn::custom_keyword!(__vy_import_marker);
+    syn::custom_keyword!(slot);
 }
 
 enum Inner {
@@ -49,28 +57,299 @@ fn inner(name: &str, input: TokenStream) -> TokenStream {
         body,
     );
 
-    let el = match el {
-        Ok(el) => el,
-        Err(err) => return err.to_compile_error().into(),
-    };
+    match el {
+        Ok(el) => expand_element(el, Modes::default()),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
 
+/// The modes that the wrapper macros `minify!`, `erased!` and `csp_nonce!`
+/// enable for the element they wrap.
+#[derive(Default)]
+struct Modes {
+    minify: bool,
+    erased: bool,
+    nonces: Option<Vec<String>>,
+}
+
+fn expand_element(el: Element, modes: Modes) -> TokenStream {
     let mut text = Buffer::new();
-    let mut ser = Serializer::new(&mut text);
+    let mut ser = if modes.minify {
+        Serializer::minified(&mut text)
+    } else {
+        Serializer::new(&mut text)
+    };
+    if let Some(elements) = modes.nonces {
+        ser = ser.with_nonces(elements);
+    }
     ser.write_element(el);
 
     let imports = ser.as_imports();
-    let parts = ser.into_parts().into_iter().map(|part| match part {
-        Part::Str(s) => quote!(::vy::PreEscaped(#s)),
-        Part::Expr(e) => quote!(::vy::IntoHtml::into_html(#e)),
-    });
+    let html = if modes.erased {
+        expand_markup(ser.into_parts())
+    } else {
+        expand_parts(ser.into_parts())
+    };
 
     quote!({
         #imports;
-        ( #(#parts),* )
+        #html
     })
     .into()
 }
 
+/// Expands an element wrapped in any number of wrapper macros, which enable
+/// `modes` for it.
+fn expand_wrapped(mut node: Node, mut modes: Modes) -> TokenStream {
+    loop {
+        let mac = match node {
+            Node::Element(el) => return expand_element(el, modes),
+            Node::Expr(Expr::Macro(ExprMacro { mac, .. })) => mac,
+            node => {
+                return syn::Error::new_spanned(
+                    node,
+                    "expected an element, such as `div!(...)`",
+                )
+                .to_compile_error()
+                .into()
+            }
+        };
+        let parsed = if mac.path.is_ident("minify") {
+            modes.minify = true;
+            mac.parse_body()
+        } else if mac.path.is_ident("erased") {
+            modes.erased = true;
+            mac.parse_body()
+        } else if mac.path.is_ident("csp_nonce") {
+            mac.parse_body().map(|NonceInput { elements, node }| {
+                modes.nonces = Some(elements);
+                node
+            })
+        } else {
+            Err(syn::Error::new_spanned(
+                mac,
+                "expected an element, such as `div!(...)`",
+            ))
+        };
+        node = match parsed {
+            Ok(node) => node,
+            Err(err) => return err.to_compile_error().into(),
+        };
+    }
+}
+
+/// Returns the node wrapped in a `minify!` call, if any, and whether it was
+/// wrapped.
+fn unwrap_minify(node: Node) -> syn::Result<(Node, bool)> {
+    match node {
+        Node::Expr(Expr::Macro(ExprMacro { mac, .. }))
+            if mac.path.is_ident("minify") =>
+        {
+            Ok((mac.parse_body()?, true))
+        }
+        node => Ok((node, false)),
+    }
+}
+
+/// Expands an element with only static content into a string literal.
+fn expand_static(node: Node) -> syn::Result<proc_macro2::TokenStream> {
+    let (node, minify) = unwrap_minify(node)?;
+    let el = match node {
+        Node::Element(el) => el,
+        node => {
+            return Err(syn::Error::new_spanned(
+                node,
+                "expected an element, such as `div!(...)`",
+            ))
+        }
+    };
+
+    let mut text = Buffer::new();
+    let mut ser = if minify {
+        Serializer::minified(&mut text)
+    } else {
+        Serializer::new(&mut text)
+    };
+    ser.write_element(el);
+    if let Some(expr) = ser.first_dynamic_value() {
+        return Err(syn::Error::new_spanned(
+            expr,
+            "only static content can be rendered at compile time",
+        ));
+    }
+
+    let imports = ser.as_imports();
+    let html = expand_static_run(&ser.into_parts());
+    Ok(quote!({
+        #imports;
+        #html
+    }))
+}
+
+/// Returns the expressions of the strings and static parts of a run.
+fn run_parts(run: &[Part]) -> Vec<proc_macro2::TokenStream> {
+    run.iter()
+        .map(|part| match part {
+            Part::Str(s, _) => quote!(#s),
+            Part::Const(e) => quote!({
+                let html: ::vy::PreEscaped<&'static str> = #e;
+                html.0
+            }),
+            Part::Expr(_) => unreachable!(),
+        })
+        .collect()
+}
+
+/// Returns whether a run contains static parts, whose length is only known at
+/// compile time.
+fn is_folded(run: &[Part]) -> bool {
+    run.iter().any(|part| matches!(part, Part::Const(_)))
+}
+
+/// Expands a run of static strings and static parts into a `&'static str`,
+/// concatenating them at compile time.
+fn expand_static_run(run: &[Part]) -> proc_macro2::TokenStream {
+    if !is_folded(run) {
+        let html: String = run
+            .iter()
+            .map(|part| match part {
+                Part::Str(s, _) => *s,
+                _ => unreachable!(),
+            })
+            .collect();
+        return quote!(#html);
+    }
+
+    let parts = run_parts(run);
+    quote!({
+        const PARTS: &[&str] = &[#(#parts),*];
+        const BYTES: [u8; ::vy::escape::concat_len(PARTS)] =
+            ::vy::escape::concat(PARTS);
+        const HTML: &str = match ::core::str::from_utf8(&BYTES) {
+            ::core::result::Result::Ok(html) => html,
+            ::core::result::Result::Err(_) => ::core::unreachable!(),
+        };
+        HTML
+    })
+}
+
+/// Expands the structural marks of a run into a `&'static [Mark]`, with
+/// offsets into the concatenated run, or returns `None` if it has none.
+fn expand_run_marks(run: &[Part]) -> Option<proc_macro2::TokenStream> {
+    let folded = is_folded(run);
+    let mut marks = Vec::new();
+    let mut start = 0;
+    for (i, part) in run.iter().enumerate() {
+        let Part::Str(s, part_marks) = part else {
+            continue;
+        };
+        for &(at, mark) in part_marks {
+            marks.push(mark.to_tokens(if folded {
+                quote!(#at + ::vy::escape::concat_len(PARTS.split_at(#i).0))
+            } else {
+                let at = start + at;
+                quote!(#at)
+            }));
+        }
+        start += s.len();
+    }
+    if marks.is_empty() {
+        return None;
+    }
+
+    if !folded {
+        return Some(quote!(&[#(#marks),*]));
+    }
+    let parts = run_parts(run);
+    Some(quote!({
+        const PARTS: &[&str] = &[#(#parts),*];
+        const MARKS: &[::vy::pretty::Mark] = &[#(#marks),*];
+        MARKS
+    }))
+}
+
+/// Expands a run of static parts into an [`IntoHtml`] value.
+///
+/// [`IntoHtml`]: vy_core::IntoHtml
+fn expand_static_value(run: &[Part]) -> proc_macro2::TokenStream {
+    let html = expand_static_run(run);
+    match expand_run_marks(run) {
+        Some(marks) => quote!(::vy::pretty::Marked(#html, #marks)),
+        None => quote!(::vy::PreEscaped(#html)),
+    }
+}
+
+/// Expands the serialized parts into a tuple of [`IntoHtml`] values.
+///
+/// [`IntoHtml`]: vy_core::IntoHtml
+fn expand_parts(parts: Vec<Part>) -> proc_macro2::TokenStream {
+    let mut values = Vec::new();
+    let mut run = Vec::new();
+
+    for part in parts {
+        match part {
+            Part::Expr(e) => {
+                if !run.is_empty() {
+                    values.push(expand_static_value(&run));
+                    run.clear();
+                }
+                values.push(quote!(::vy::IntoHtml::into_html(#e)));
+            }
+            part => run.push(part),
+        }
+    }
+    if !run.is_empty() {
+        values.push(expand_static_value(&run));
+    }
+
+    quote!(( #(#values),* ))
+}
+
+/// Expands the serialized parts into a type-erased [`Markup`].
+///
+/// [`Markup`]: vy_core::Markup
+fn expand_markup(parts: Vec<Part>) -> proc_macro2::TokenStream {
+    let mut statics = vec![Vec::new()];
+    let mut slots = Vec::new();
+
+    for part in parts {
+        match part {
+            Part::Expr(e) => {
+                slots.push(quote!(::vy::Markup::slot(
+                    ::vy::IntoHtml::into_html(#e)
+                )));
+                statics.push(Vec::new());
+            }
+            part => statics.last_mut().unwrap().push(part),
+        }
+    }
+
+    // Calls in `const` blocks are not promoted to `'static`, so the slices
+    // need named constants.
+    let folded = statics.iter().any(|run| is_folded(run));
+    let marks: Vec<_> =
+        statics.iter().map(|run| expand_run_marks(run)).collect();
+    let marked = marks.iter().any(Option::is_some);
+    let marks = marks
+        .into_iter()
+        .map(|marks| marks.unwrap_or_else(|| quote!(&[])));
+    let statics = statics.iter().map(|run| expand_static_run(run));
+    let slots = quote!(::core::convert::From::from([#(#slots),*]));
+    if !folded {
+        return if marked {
+            quote!(::vy::Markup::marked(&[#(#statics),*], &[#(#marks),*], #slots))
+        } else {
+            quote!(::vy::Markup::new(&[#(#statics),*], #slots))
+        };
+    }
+
+    quote!({
+        const STATICS: &[&str] = &[#(#statics),*];
+        const MARKS: &[&[::vy::pretty::Mark]] = &[#(#marks),*];
+        ::vy::Markup::marked(STATICS, MARKS, #slots)
+    })
+}
+
 macro_rules! define_proc_macro {
     ($($(#[doc=$doc:literal])* $el:ident)+) => {
         $(
@@ -84,3 +363,423 @@ macro_rules! define_proc_macro {
 }
 
 for_all_elements!(define_proc_macro);
+
+/// Minifies the static content of an element at compile time.
+///
+/// Runs of whitespace in text are collapsed into a single space, except
+/// inside `pre` and `textarea`, the static content of `script` and `style`
+/// elements is minified, and quotes are dropped around static attribute
+/// values that do not need them. Dynamic values are rendered unchanged, and
+/// so are elements that are built by other templates.
+///
+/// ```
+/// # use vy::prelude::*;
+/// let page = minify!(div!(
+///     class = "card",
+///     style!("p {\n  color: red;\n}"),
+///     p!("Hello,\n    world!")
+/// ));
+/// assert_eq!(
+///     page.into_string(),
+///     "<div class=card><style>p{color:red}</style><p>Hello, world!</p></div>"
+/// );
+/// ```
+///
+/// Since the content of scripts and styles changes, pass the `minify!`
+/// element to [`csp_hash!`] when hashing it.
+#[proc_macro]
+pub fn minify(input: TokenStream) -> TokenStream {
+    let node = parse_macro_input!(input as Node);
+    expand_wrapped(
+        node,
+        Modes {
+            minify: true,
+            ..Modes::default()
+        },
+    )
+}
+
+/// Expands an element into a type-erased [`Markup`], which compiles faster
+/// than the nested tuples that element macros expand to.
+///
+/// The static text of the element, including that of nested element macros,
+/// is stored in a table of strings, and each dynamic value is boxed behind a
+/// trait object. This trades one allocation per `erased!` call and a dynamic
+/// call per value for far fewer generic instantiations, which can dominate
+/// the build times of large templates. The element may be wrapped in
+/// [`minify!`] or [`csp_nonce!`].
+///
+/// ```
+/// # use vy::prelude::*;
+/// fn page<'a>(title: &'a str, rows: &'a [u32]) -> impl IntoHtml + 'a {
+///     erased!(html!(
+///         head!(title!(title)),
+///         body!(ul!(rows.iter().map(|row| li!(*row))))
+///     ))
+/// }
+///
+/// assert_eq!(
+///     page("Rows", &[1, 2]).into_string(),
+///     "<html><head><title>Rows</title></head><body><ul><li>1</li><li>2</\
+///      li></ul></body></html>"
+/// );
+/// ```
+///
+/// Since the values are trait objects, functions returning erased markup
+/// must name the lifetime of borrowed or generic arguments, as above.
+///
+/// [`Markup`]: vy_core::Markup
+#[proc_macro]
+pub fn erased(input: TokenStream) -> TokenStream {
+    let node = parse_macro_input!(input as Node);
+    expand_wrapped(
+        node,
+        Modes {
+            erased: true,
+            ..Modes::default()
+        },
+    )
+}
+
+/// Adds a CSP nonce to the `script`, `style` and stylesheet `link` elements
+/// of an element.
+///
+/// The `nonce` attribute is rendered when a [`Nonce`] is provided in the
+/// render context, and left out otherwise. Elements that already have a
+/// `nonce` attribute are left as is, and so are `link` elements without a
+/// literal `rel` attribute naming a stylesheet or a preload.
+///
+/// ```
+/// # use vy::{csp::Nonce, prelude::*};
+/// let head = || csp_nonce!(head!(script!(src = "/app.js"), style!("p{}")));
+///
+/// let mut buf = Buffer::new();
+/// buf.provide(Nonce::new("r4nd0m"));
+/// head().render_into(&mut buf);
+/// assert_eq!(
+///     buf.into_string(),
+///     "<head><script src=\"/app.js\" nonce=\"r4nd0m\"></script><style \
+///      nonce=\"r4nd0m\">p{}</style></head>"
+/// );
+/// ```
+///
+/// The elements that get a nonce can be listed before the element instead,
+/// as in `csp_nonce!([script, template], body!(..))`. Only the element
+/// macros nested in the wrapped element are affected, not those of other
+/// templates it renders, and the element may be wrapped in [`minify!`] or
+/// [`erased!`].
+///
+/// [`Nonce`]: vy_core::csp::Nonce
+#[proc_macro]
+pub fn csp_nonce(input: TokenStream) -> TokenStream {
+    let NonceInput { elements, node } = parse_macro_input!(input as NonceInput);
+    expand_wrapped(
+        node,
+        Modes {
+            nonces: Some(elements),
+            ..Modes::default()
+        },
+    )
+}
+
+/// Renders an element with only static content into a `&'static str` at
+/// compile time.
+///
+/// Any dynamic value in the element is an error, which makes the result
+/// usable in `const` and `static` items. The element may be wrapped in
+/// [`minify!`]. Since a nonce can only be added when rendering, use
+/// [`csp_hash!`] to allow inline scripts and styles instead.
+///
+/// ```
+/// # use vy::prelude::*;
+/// const FOOTER: &str = static_html!(footer!(
+///     class = "site",
+///     p!("Made with ", a!(href = "https://crates.io/crates/vy", "vy"))
+/// ));
+/// assert_eq!(
+///     FOOTER,
+///     "<footer class=\"site\"><p>Made with <a \
+///      href=\"https://crates.io/crates/vy\">vy</a></p></footer>"
+/// );
+/// ```
+///
+/// Rendering the string with [`PreEscaped`] embeds it in other templates,
+/// and returning it from a `const fn` lets them inline it with
+/// [`static_part!`], such as `body!(static_part!(nav()), ..)`.
+///
+/// ```compile_fail
+/// # use vy::prelude::*;
+/// let name = "Ann";
+/// let greeting = static_html!(p!("Hello, ", name));
+/// ```
+///
+/// [`PreEscaped`]: vy_core::escape::PreEscaped
+#[proc_macro]
+pub fn static_html(input: TokenStream) -> TokenStream {
+    let node = parse_macro_input!(input as Node);
+    expand_static(node)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Inlines static HTML into the static parts of the enclosing element.
+///
+/// The expression must be a constant [`PreEscaped<&'static str>`], such as
+/// the result of a `const fn` returning [`static_html!`]. Its HTML is merged
+/// with the surrounding static parts at compile time, so it costs nothing at
+/// render time, even in templates that are not fully static. Anything else
+/// in a template, including a `const` block, is rendered as a value.
+///
+/// ```
+/// # use vy::prelude::*;
+/// const fn nav() -> PreEscaped<&'static str> {
+///     PreEscaped(static_html!(nav!(a!(href = "/", "Home"))))
+/// }
+///
+/// let name = "Ann";
+/// assert_eq!(
+///     body!(static_part!(nav()), main!("Hello, ", name)).into_string(),
+///     "<body><nav><a href=\"/\">Home</a></nav><main>Hello, Ann</main></body>"
+/// );
+/// ```
+///
+/// [`PreEscaped<&'static str>`]: vy_core::escape::PreEscaped
+#[proc_macro]
+pub fn static_part(input: TokenStream) -> TokenStream {
+    let expr = parse_macro_input!(input as Expr);
+    quote!({
+        const HTML: ::vy::PreEscaped<&'static str> = #expr;
+        HTML
+    })
+    .into()
+}
+
+/// Computes the CSP hash source of a static inline script or style at compile
+/// time.
+///
+/// Accepts a string literal, or a `script!` or `style!` element with static
+/// content, optionally wrapped in [`minify!`], and expands to a
+/// `&'static str` such as `'sha256-...'`, ready to be used in a `script-src`
+/// or `style-src` directive.
+///
+/// ```
+/// # use vy::prelude::*;
+/// const HASH: &str = csp_hash!(script!("alert('Hello, world.');"));
+/// assert_eq!(
+///     HASH,
+///     "'sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng='"
+/// );
+/// ```
+#[proc_macro]
+pub fn csp_hash(input: TokenStream) -> TokenStream {
+    let node = parse_macro_input!(input as Node);
+    csp::hash(node)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Builds a URL from a path template and query parameters, percent-encoding
+/// the dynamic parts.
+///
+/// Placeholders such as `{id}` in the template are replaced by the value
+/// bound after the template, or by the variable of the same name, and are
+/// encoded as a single path segment. Query parameters follow a `;`, and
+/// parameters marked with `?` take an `Option` and are left out when it is
+/// `None`. All values must implement [`Display`].
+///
+/// The result implements [`IntoHtml`] and [`Display`].
+///
+/// ```
+/// # use vy::prelude::*;
+/// let user = "ann/bob";
+/// let query = Some("a&b");
+/// let url = url!("/users/{user}/posts/{id}", id = 7; page = 2, q? = query);
+/// assert_eq!(url.to_string(), "/users/ann%2Fbob/posts/7?page=2&q=a%26b");
+/// ```
+///
+/// [`Display`]: core::fmt::Display
+/// [`IntoHtml`]: vy_core::IntoHtml
+#[proc_macro]
+pub fn url(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as url::UrlInput);
+    url::expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Declares a layout with named slots.
+///
+/// The layout is a struct with a type parameter per slot, which defaults to
+/// [`Unset`] and is replaced by the content of the slot when it is filled.
+/// `new()` creates the layout with every slot unset, and a method named after
+/// each slot fills it. Unset slots render their default content, which is
+/// only evaluated if needed. The expression after `=>` is the HTML of the
+/// layout, with the content of each slot bound to a variable of the same
+/// name. A layout with a `children` slot can also be called like a function
+/// with its children, as in `Page(p!("Hi"))`.
+///
+/// ```
+/// # use vy::prelude::*;
+/// layout! {
+///     struct Card {
+///         /// The heading of the card.
+///         title = "Untitled",
+///         body = (),
+///     } => article!(class = "card", h2!(title), body)
+/// }
+///
+/// assert_eq!(
+///     Card::new().body(p!("Hi")).into_string(),
+///     "<article class=\"card\"><h2>Untitled</h2><p>Hi</p></article>"
+/// );
+/// ```
+///
+/// See the [`layout`] module for details.
+///
+/// ```compile_fail
+/// # use vy::prelude::*;
+/// layout! {
+///     struct Card { title = "Untitled" } => article!(h2!(title))
+/// }
+///
+/// let card = Card::new().subtitle("Oops");
+/// ```
+///
+/// [`Unset`]: vy_core::layout::Unset
+/// [`layout`]: vy_core::layout
+#[proc_macro]
+pub fn layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as layout::Layout);
+    layout::expand(input).into()
+}
+
+/// Marks a named fragment of a template, which can be rendered on its own
+/// with [`fragment::render`].
+///
+/// The name is followed by the content of the fragment, as in element
+/// macros. Outside of [`fragment::render`], the fragment renders its content
+/// as usual.
+///
+/// ```
+/// # use vy::{fragment, prelude::*};
+/// let page =
+///     body!(h1!("Shop"), fragment!("cart", div!(id = "cart", "3 items")));
+/// assert_eq!(
+///     fragment::render(page, "cart").unwrap(),
+///     "<div id=\"cart\">3 items</div>"
+/// );
+/// ```
+///
+/// [`fragment::render`]: vy_core::fragment::render
+#[proc_macro]
+pub fn fragment(input: TokenStream) -> TokenStream {
+    let FragmentInput { name, nodes } =
+        parse_macro_input!(input as FragmentInput);
+
+    let mut text = Buffer::new();
+    let mut ser = Serializer::new(&mut text);
+    for node in nodes {
+        ser.write_node(node);
+    }
+
+    let imports = ser.as_imports();
+    let html = expand_parts(ser.into_parts());
+
+    quote!({
+        #imports;
+        ::vy::fragment::Fragment::new(#name, #html)
+    })
+    .into()
+}
+
+/// Turns a function into a component with named slots.
+///
+/// Each parameter of the function becomes a slot, and the function body the
+/// HTML of the component, as with [`layout!`]. Since a slot takes any
+/// [`IntoHtml`] value, every parameter must be an `impl IntoHtml`. Slots are
+/// empty unless the parameter has a `#[slot(default = ...)]` attribute.
+///
+/// Inside element macros, a component is called with its slots filled by
+/// `slot name = value`, and any other arguments fill the `children` slot:
+///
+/// ```
+/// # use vy::prelude::*;
+/// #[component]
+/// fn Modal(
+///     #[slot(default = h2!("Notice"))] header: impl IntoHtml,
+///     children: impl IntoHtml,
+///     footer: impl IntoHtml,
+/// ) -> impl IntoHtml {
+///     div!(class = "modal", header, div!(children), footer)
+/// }
+///
+/// let html = body!(Modal(
+///     slot footer = button!("Close"),
+///     p!("Saved."),
+///     p!("Nothing else to do.")
+/// ));
+/// assert_eq!(
+///     html.into_string(),
+///     "<body><div class=\"modal\"><h2>Notice</h2><div><p>Saved.</p><p>Nothing \
+///      else to do.</p></div><button>Close</button></div></body>"
+/// );
+/// ```
+///
+/// Outside of element macros, the slots are filled with methods, as in
+/// `Modal::new().footer(..)`. A component with a `children` slot can also be
+/// called like a function with its children alone, as in
+/// `Modal(p!("Saved."))`, anywhere. Filling a slot the component does not
+/// declare is a compile error.
+///
+/// ```compile_fail
+/// # use vy::prelude::*;
+/// #[component]
+/// fn Modal(header: impl IntoHtml) -> impl IntoHtml {
+///     div!(header)
+/// }
+///
+/// let html = body!(Modal(slot title = "Oops"));
+/// ```
+///
+/// ```compile_fail
+/// # use vy::prelude::*;
+/// #[component]
+/// fn Modal(header: &str) -> impl IntoHtml {
+///     div!(header)
+/// }
+/// ```
+///
+/// [`IntoHtml`]: vy_core::IntoHtml
+#[proc_macro_attribute]
+pub fn component(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "expected `#[component]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let input = parse_macro_input!(input as ItemFn);
+    layout::component(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derives [`Route`] for an enum of routes, along with [`IntoHtml`] and
+/// [`Display`].
+///
+/// Every variant needs a `#[route("/path/{field}")]` attribute. See the
+/// [`route`] module for details.
+///
+/// [`Display`]: core::fmt::Display
+/// [`IntoHtml`]: vy_core::IntoHtml
+/// [`Route`]: vy_core::route::Route
+/// [`route`]: vy_core::route
+#[proc_macro_derive(Route, attributes(route))]
+pub fn derive_route(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    route::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
@@ -0,0 +1,267 @@
+//! Compile-time minification of static template content.
+//!
+//! Everything here is conservative: the output must render exactly like the
+//! input, and input that cannot be handled safely is returned unchanged.
+
+#[inline]
+fn is_whitespace(ch: char) -> bool {
+    matches!(ch, '\t' | '\n' | '\x0c' | '\r' | ' ')
+}
+
+/// Collapses each run of HTML whitespace in `text` into a single space.
+pub fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if is_whitespace(ch) {
+            while chars.next_if(|ch| is_whitespace(*ch)).is_some() {}
+            out.push(' ');
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Returns whether an attribute value can be written without quotes.
+pub fn is_unquoted_safe(value: &str) -> bool {
+    !value.is_empty()
+        && !value.contains(|ch: char| {
+            is_whitespace(ch)
+                || matches!(ch, '"' | '\'' | '=' | '<' | '>' | '`')
+        })
+}
+
+/// Minifies a style sheet by removing comments and the whitespace around
+/// punctuation.
+pub fn css(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut space = false;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(ch) => prev = ch,
+                        None => return src.to_owned(),
+                    }
+                }
+                space = true;
+            }
+            ch if is_whitespace(ch) => space = true,
+            '"' | '\'' => {
+                flush_css_space(&mut out, &mut space, ch);
+                out.push(ch);
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            out.push('\\');
+                            match chars.next() {
+                                Some(ch) => out.push(ch),
+                                None => return src.to_owned(),
+                            }
+                        }
+                        Some(c) if c == ch => {
+                            out.push(c);
+                            break;
+                        }
+                        Some(c) => out.push(c),
+                        None => return src.to_owned(),
+                    }
+                }
+            }
+            '}' if out.ends_with(';') => {
+                out.pop();
+                out.push('}');
+                space = false;
+            }
+            ch => {
+                flush_css_space(&mut out, &mut space, ch);
+                out.push(ch);
+            }
+        }
+    }
+
+    out
+}
+
+/// Writes a pending space before `next`, unless it is next to punctuation.
+fn flush_css_space(out: &mut String, space: &mut bool, next: char) {
+    if !std::mem::take(space) {
+        return;
+    }
+    let separates = |ch: char| matches!(ch, '{' | '}' | ';' | ',');
+    match out.chars().next_back() {
+        Some(prev) if !separates(prev) && prev != ':' && !separates(next) => {
+            out.push(' ')
+        }
+        _ => {}
+    }
+}
+
+/// Minifies a script by removing comments and redundant whitespace.
+///
+/// Line breaks are kept where they may end a statement, and scripts with
+/// template literal substitutions are returned unchanged.
+pub fn js(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    // The pending whitespace: `Some(true)` if it contains a line break.
+    let mut space: Option<bool> = None;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|ch| *ch != '\n').is_some() {}
+                space = Some(true);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                let mut newline = false;
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(ch) => {
+                            newline |= ch == '\n';
+                            prev = ch;
+                        }
+                        None => return src.to_owned(),
+                    }
+                }
+                space = Some(space.unwrap_or(false) | newline);
+            }
+            ch if is_whitespace(ch) => {
+                space = Some(space.unwrap_or(false) | (ch == '\n'));
+            }
+            '"' | '\'' | '`' => {
+                flush_js_space(&mut out, &mut space, ch);
+                out.push(ch);
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            out.push('\\');
+                            match chars.next() {
+                                Some(ch) => out.push(ch),
+                                None => return src.to_owned(),
+                            }
+                        }
+                        Some('$')
+                            if ch == '`' && chars.peek() == Some(&'{') =>
+                        {
+                            return src.to_owned();
+                        }
+                        Some(c) if c == ch => {
+                            out.push(c);
+                            break;
+                        }
+                        Some('\n') if ch != '`' => return src.to_owned(),
+                        Some(c) => out.push(c),
+                        None => return src.to_owned(),
+                    }
+                }
+            }
+            '/' if starts_regex(&out) => {
+                flush_js_space(&mut out, &mut space, ch);
+                out.push('/');
+                let mut class = false;
+                loop {
+                    match chars.next() {
+                        Some('\\') => {
+                            out.push('\\');
+                            match chars.next() {
+                                Some(ch) => out.push(ch),
+                                None => return src.to_owned(),
+                            }
+                        }
+                        Some('/') if !class => {
+                            out.push('/');
+                            break;
+                        }
+                        Some('\n') | None => return src.to_owned(),
+                        Some(ch) => {
+                            match ch {
+                                '[' => class = true,
+                                ']' => class = false,
+                                _ => {}
+                            }
+                            out.push(ch);
+                        }
+                    }
+                }
+            }
+            ch => {
+                flush_js_space(&mut out, &mut space, ch);
+                out.push(ch);
+            }
+        }
+    }
+
+    out
+}
+
+/// Returns whether a `/` after `out` starts a regular expression literal,
+/// rather than being a division.
+///
+/// This is the case after punctuation and after keywords that are followed
+/// by an expression, such as `return /a b/.test(s)`.
+fn starts_regex(out: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "await",
+        "case",
+        "delete",
+        "do",
+        "else",
+        "in",
+        "instanceof",
+        "new",
+        "of",
+        "return",
+        "throw",
+        "typeof",
+        "void",
+        "yield",
+    ];
+
+    let out = out.trim_end();
+    match out.chars().next_back() {
+        None => true,
+        Some(prev) if is_js_word(prev) => {
+            let start = out.trim_end_matches(is_js_word).len();
+            // A property such as `a.return` is not a keyword.
+            !out[..start].trim_end().ends_with('.')
+                && KEYWORDS.contains(&&out[start..])
+        }
+        Some(prev) => "(,=:[!&|?{};+-*%<>~^".contains(prev),
+    }
+}
+
+/// Returns whether `ch` may be part of a JavaScript identifier or number.
+fn is_js_word(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '$' | '\\')
+}
+
+/// Writes pending whitespace before `next`, if it separates tokens or may
+/// end a statement.
+fn flush_js_space(out: &mut String, space: &mut Option<bool>, next: char) {
+    let Some(newline) = space.take() else {
+        return;
+    };
+    let Some(prev) = out.chars().next_back() else {
+        return;
+    };
+
+    if newline && !matches!(prev, '{' | ';' | ',' | '(' | '[') {
+        out.push('\n');
+    } else if (is_js_word(prev) && is_js_word(next))
+        || (matches!(prev, '+' | '-' | '/') && matches!(next, '+' | '-' | '/'))
+        || (prev.is_ascii_digit() && next == '.')
+    {
+        out.push(' ');
+    }
+}
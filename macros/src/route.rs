@@ -0,0 +1,240 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    spanned::Spanned, Data, DeriveInput, Error, Fields, Ident, LitStr, Member,
+    Result, Variant,
+};
+
+enum Segment {
+    Literal(String),
+    Field(Member),
+}
+
+/// Parses the `#[route("...")]` attribute of a variant into its path
+/// segments.
+fn parse_route(variant: &Variant) -> Result<Vec<Segment>> {
+    let mut attrs = variant.attrs.iter().filter(|a| a.path().is_ident("route"));
+    let Some(attr) = attrs.next() else {
+        return Err(Error::new_spanned(
+            variant,
+            "missing `#[route(\"/path\")]` attribute",
+        ));
+    };
+    if let Some(attr) = attrs.next() {
+        return Err(Error::new_spanned(attr, "duplicate `#[route]` attribute"));
+    }
+
+    let template = attr.parse_args::<LitStr>()?;
+    let value = template.value();
+    let Some(path) = value.strip_prefix('/') else {
+        return Err(Error::new_spanned(template, "route must start with `/`"));
+    };
+    if path.contains(['?', '#']) {
+        return Err(Error::new_spanned(
+            template,
+            "route must not contain a query or fragment",
+        ));
+    }
+
+    path.split('/')
+        .map(|segment| {
+            let Some(name) =
+                segment.strip_prefix('{').and_then(|s| s.strip_suffix('}'))
+            else {
+                if segment.contains(['{', '}']) {
+                    return Err(Error::new_spanned(
+                        &template,
+                        "a placeholder must span a whole path segment",
+                    ));
+                }
+                return Ok(Segment::Literal(segment.to_owned()));
+            };
+            match name.parse::<usize>() {
+                Ok(index) => Ok(Segment::Field(Member::from(index))),
+                Err(_) => syn::parse_str::<Ident>(name)
+                    .map(|ident| Segment::Field(Member::Named(ident)))
+                    .map_err(|_| {
+                        Error::new_spanned(
+                            &template,
+                            format!("invalid placeholder `{{{name}}}`"),
+                        )
+                    }),
+            }
+        })
+        .collect()
+}
+
+/// Returns the variable that a field is bound to in patterns.
+fn field_var(member: &Member) -> Ident {
+    match member {
+        Member::Named(ident) => {
+            format_ident!("__vy_{}", ident, span = Span::mixed_site())
+        }
+        Member::Unnamed(index) => {
+            format_ident!("__vy_{}", index.index, span = Span::mixed_site())
+        }
+    }
+}
+
+/// Expands `#[derive(Route)]` into implementations of `Route`, `IntoHtml`
+/// and `Display`.
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`Route` can only be derived for enums",
+        ));
+    };
+
+    let mut write_arms = Vec::new();
+    let mut parsers = Vec::new();
+    for variant in &data.variants {
+        let segments = parse_route(variant)?;
+        let name = &variant.ident;
+
+        let members: Vec<Member> = match &variant.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(|f| Member::Named(f.ident.clone().unwrap()))
+                .collect(),
+            Fields::Unnamed(fields) => {
+                (0..fields.unnamed.len()).map(Member::from).collect()
+            }
+            Fields::Unit => Vec::new(),
+        };
+
+        for segment in &segments {
+            if let Segment::Field(member) = segment {
+                if !members.contains(member) {
+                    return Err(Error::new(
+                        member.span(),
+                        format!("`{name}` has no field `{}`", quote!(#member)),
+                    ));
+                }
+            }
+        }
+        for member in &members {
+            let count = segments
+                .iter()
+                .filter(|s| matches!(s, Segment::Field(m) if m == member))
+                .count();
+            if count != 1 {
+                return Err(Error::new_spanned(
+                    variant,
+                    format!(
+                        "field `{}` must appear exactly once in the route",
+                        quote!(#member)
+                    ),
+                ));
+            }
+        }
+
+        let vars: Vec<Ident> = members.iter().map(field_var).collect();
+        let pattern = match &variant.fields {
+            Fields::Unit => quote!(Self::#name),
+            _ => quote!(Self::#name { #(#members: #vars),* }),
+        };
+
+        // Merge consecutive literal segments into a single write.
+        let w = Ident::new("w", Span::mixed_site());
+        let mut writes = Vec::new();
+        let mut text = String::new();
+        for segment in &segments {
+            text.push('/');
+            match segment {
+                Segment::Literal(literal) => text.push_str(literal),
+                Segment::Field(member) => {
+                    let var = field_var(member);
+                    writes.push(quote!(#w.text(#text);));
+                    writes.push(quote!(#w.segment(#var);));
+                    text.clear();
+                }
+            }
+        }
+        if !text.is_empty() {
+            writes.push(quote!(#w.text(#text);));
+        }
+        write_arms.push(quote!(#pattern => { #(#writes)* }));
+
+        let s = Ident::new("segments", Span::mixed_site());
+        let checks = segments.iter().map(|segment| match segment {
+            Segment::Literal(literal) => quote! {
+                if #s.next() != ::core::option::Option::Some(#literal) {
+                    break 'route;
+                }
+            },
+            Segment::Field(member) => {
+                let var = field_var(member);
+                quote! {
+                    let ::core::option::Option::Some(#var) = #s
+                        .next()
+                        .and_then(::vy::route::parse_segment)
+                    else {
+                        break 'route;
+                    };
+                }
+            }
+        });
+        parsers.push(quote! {
+            'route: {
+                let mut #s = ::vy::route::segments(path);
+                if #s.next() != ::core::option::Option::Some("") {
+                    break 'route;
+                }
+                #(#checks)*
+                if #s.next().is_some() {
+                    break 'route;
+                }
+                return ::core::option::Option::Some(#pattern);
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let w = Ident::new("w", Span::mixed_site());
+    let f = Ident::new("f", Span::mixed_site());
+
+    let mut ref_generics = input.generics.clone();
+    ref_generics.params.insert(0, syn::parse_quote!('__vy_a));
+    let (ref_impl_generics, _, _) = ref_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::vy::route::Route for #ident #ty_generics #where_clause {
+            fn write_path(&self, #w: &mut ::vy::url::UrlWriter) {
+                match self {
+                    #(#write_arms)*
+                }
+            }
+
+            fn from_path(path: &str) -> ::core::option::Option<Self> {
+                #(#parsers)*
+                ::core::option::Option::None
+            }
+        }
+
+        impl #impl_generics ::vy::IntoHtml for #ident #ty_generics #where_clause {
+            #[inline]
+            fn into_html(self) -> impl ::vy::IntoHtml {
+                ::vy::url::Url::new(move |#w: &mut ::vy::url::UrlWriter| {
+                    ::vy::route::Route::write_path(&self, #w)
+                })
+            }
+        }
+
+        impl #ref_impl_generics ::vy::IntoHtml for &'__vy_a #ident #ty_generics #where_clause {
+            #[inline]
+            fn into_html(self) -> impl ::vy::IntoHtml {
+                ::vy::route::Route::url(self)
+            }
+        }
+
+        impl #impl_generics ::core::fmt::Display for #ident #ty_generics #where_clause {
+            fn fmt(&self, #f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                ::core::fmt::Display::fmt(&::vy::route::Route::url(self), #f)
+            }
+        }
+    })
+}
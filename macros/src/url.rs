@@ -0,0 +1,202 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse::{Parse, ParseStream},
+    Error, Expr, Ident, LitStr, Result, Token,
+};
+
+use crate::ast::Attr;
+
+/// The input of the `url!` macro: a path template, followed by the values
+/// of its placeholders and the query parameters.
+///
+/// ```text
+/// url := template [',' binding],* [';' [param],*]
+/// binding := identifier '=' expression
+/// param := name['?'] '=' expression
+/// ```
+pub struct UrlInput {
+    template: LitStr,
+    bindings: Vec<(Ident, Expr)>,
+    params: Vec<Attr>,
+}
+
+impl Parse for UrlInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let template = input.parse()?;
+
+        let mut bindings = Vec::new();
+        while input.parse::<Option<Token![,]>>()?.is_some() {
+            if input.is_empty() || input.peek(Token![;]) {
+                break;
+            }
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            bindings.push((name, input.parse()?));
+        }
+
+        let mut params = Vec::new();
+        if input.parse::<Option<Token![;]>>()?.is_some() {
+            while !input.is_empty() {
+                params.push(input.parse()?);
+                if input.parse::<Option<Token![,]>>()?.is_none() {
+                    break;
+                }
+            }
+        }
+
+        if !input.is_empty() {
+            return Err(input.error("expected `,` or `;`"));
+        }
+
+        Ok(Self {
+            template,
+            bindings,
+            params,
+        })
+    }
+}
+
+enum Piece {
+    Text(String),
+    Placeholder(String),
+}
+
+/// Splits a template into literal text and `{name}` placeholders.
+fn parse_template(template: &LitStr) -> Result<Vec<Piece>> {
+    let value = template.value();
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => name.push(ch),
+                        None => {
+                            return Err(Error::new_spanned(
+                                template,
+                                "unclosed `{` in URL template",
+                            ))
+                        }
+                    }
+                }
+                if syn::parse_str::<Ident>(&name).is_err() {
+                    return Err(Error::new_spanned(
+                        template,
+                        format!("invalid placeholder `{{{name}}}`"),
+                    ));
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Placeholder(name));
+            }
+            '}' => {
+                return Err(Error::new_spanned(
+                    template,
+                    "unmatched `}` in URL template, use `}}` to escape it",
+                ))
+            }
+            _ => text.push(ch),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+
+    Ok(pieces)
+}
+
+/// Expands to a `::vy::url::Url` that writes the percent-encoded URL.
+pub fn expand(input: UrlInput) -> Result<TokenStream> {
+    let pieces = parse_template(&input.template)?;
+
+    let mut lets = Vec::new();
+    let mut used = vec![false; input.bindings.len()];
+    let binding_var =
+        |i: usize| format_ident!("__vy_{}", i, span = Span::mixed_site());
+
+    for (i, (name, value)) in input.bindings.iter().enumerate() {
+        if input.bindings[..i].iter().any(|(prev, _)| prev == name) {
+            return Err(Error::new_spanned(name, "duplicate binding"));
+        }
+        let var = binding_var(i);
+        lets.push(quote!(let #var = #value;));
+    }
+
+    let w = Ident::new("w", Span::mixed_site());
+    let mut writes = Vec::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => writes.push(quote!(#w.text(#text);)),
+            Piece::Placeholder(name) => {
+                let var = match input
+                    .bindings
+                    .iter()
+                    .position(|(binding, _)| *binding == name)
+                {
+                    Some(i) => {
+                        used[i] = true;
+                        binding_var(i)
+                    }
+                    // Like `format!`, capture a variable of the same name.
+                    None => {
+                        let var = format_ident!(
+                            "__vy_{}",
+                            name,
+                            span = Span::mixed_site()
+                        );
+                        let ident = Ident::new(&name, input.template.span());
+                        lets.push(quote!(let #var = &#ident;));
+                        var
+                    }
+                };
+                writes.push(quote!(#w.segment(&#var);));
+            }
+        }
+    }
+
+    if let Some(i) = used.iter().position(|used| !used) {
+        return Err(Error::new_spanned(
+            &input.bindings[i].0,
+            "binding is not used in the URL template",
+        ));
+    }
+
+    for (i, param) in input.params.into_iter().enumerate() {
+        let name = param.name.to_string();
+        let var = format_ident!("__vy_param_{}", i, span = Span::mixed_site());
+        let value = Expr::from(param.value);
+        lets.push(quote!(let #var = #value;));
+        writes.push(if param.question_token.is_some() {
+            let val = Ident::new("val", Span::mixed_site());
+            quote_spanned! {Span::mixed_site()=>
+                if let ::core::option::Option::Some(#val) = &#var {
+                    #w.param(#name, #val);
+                }
+            }
+        } else {
+            quote!(#w.param(#name, &#var);)
+        });
+    }
+
+    Ok(quote!({
+        #(#lets)*
+        ::vy::url::Url::new(move |#w: &mut ::vy::url::UrlWriter<'_>| {
+            #(#writes)*
+        })
+    }))
+}
@@ -1,3 +1,4 @@
+#![cfg_attr(not(test), no_std)]
 #![doc = include_str!("../README.md")]
 
 extern crate self as vy;
@@ -97,6 +98,629 @@ mod tests {
         );
     }
 
+    #[test]
+    fn erased_markup() {
+        fn page<'a>(name: &'a str, items: &'a [u8]) -> impl IntoHtml + 'a {
+            erased!(div!(
+                class = "page",
+                h1!("Hi ", name),
+                ul!(items.iter().map(|item| li!(*item)))
+            ))
+        }
+
+        let items = [1, 2];
+        let tuple = div!(
+            class = "page",
+            h1!("Hi ", "<Ann>"),
+            ul!(items.iter().map(|item| li!(*item)))
+        );
+        assert_eq!(page("<Ann>", &items).into_string(), tuple.into_string());
+        assert_eq!(
+            erased!(minify!(p!("a  b", br!()))).into_string(),
+            "<p>a b<br></p>"
+        );
+    }
+
+    #[test]
+    fn fallible_component() {
+        fn item(id: &str) -> Result<impl IntoHtml, Error> {
+            let id: u32 = id.parse()?;
+            Ok(li!(id))
+        }
+
+        assert_eq!(
+            ul!(item("1"), item("2")).try_into_string().unwrap(),
+            "<ul><li>1</li><li>2</li></ul>"
+        );
+        assert!(ul!(item("1"), item("x")).try_into_string().is_err());
+    }
+
+    #[test]
+    fn csp_nonce() {
+        let rel = "stylesheet";
+        let page = || {
+            csp_nonce!(head!(
+                link!(rel = "icon", href = "favicon.ico"),
+                link!(rel = "stylesheet", href = "style.css"),
+                link!(rel = rel, href = "other.css"),
+                script!(nonce = "fixed", src = "a.js"),
+                script!(src = "b.js")
+            ))
+        };
+
+        let mut buf = Buffer::new();
+        buf.provide(csp::Nonce::new("abc"));
+        page().render_into(&mut buf);
+        assert_eq!(
+            buf.into_string(),
+            "<head><link rel=\"icon\" href=\"favicon.ico\"><link \
+             rel=\"stylesheet\" href=\"style.css\" nonce=\"abc\"><link \
+             rel=\"stylesheet\" href=\"other.css\"><script nonce=\"fixed\" \
+             src=\"a.js\"></script><script src=\"b.js\" \
+             nonce=\"abc\"></script></head>"
+        );
+        assert_eq!(
+            page().into_string(),
+            "<head><link rel=\"icon\" href=\"favicon.ico\"><link \
+             rel=\"stylesheet\" href=\"style.css\"><link rel=\"stylesheet\" \
+             href=\"other.css\"><script nonce=\"fixed\" \
+             src=\"a.js\"></script><script src=\"b.js\"></script></head>"
+        );
+
+        let mut buf = Buffer::new();
+        buf.provide(csp::Nonce::new("abc"));
+        (
+            script!(),
+            csp_nonce!([template], body!(template!(), script!())),
+            erased!(minify!(csp_nonce!(style!("p {}")))),
+        )
+            .render_into(&mut buf);
+        assert_eq!(
+            buf.into_string(),
+            "<script></script><body><template \
+             nonce=\"abc\"></template><script></script></body><style \
+             nonce=\"abc\">p{}</style>"
+        );
+    }
+
+    #[test]
+    fn csp_hash() {
+        assert_eq!(
+            csp_hash!("alert('Hello, world.');"),
+            "'sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng='"
+        );
+        assert_eq!(
+            csp_hash!(style!("body{margin:0}")),
+            csp_hash!("body{margin:0}")
+        );
+    }
+
+    #[test]
+    fn exact_size() {
+        let names = ["Ann & Bob", "<Eve>"];
+        let page = || {
+            ul!(
+                class = "users",
+                names.iter().map(|name| li!(*name)).collect::<Vec<_>>(),
+                li!(data_n = 42, 1.25, " ", '"')
+            )
+        };
+        let html = page().into_string_exact();
+        assert_eq!(html, page().into_string());
+        assert_eq!(html.capacity(), html.len());
+    }
+
+    #[test]
+    fn output_limits() {
+        use limit::{nested, LimitExceeded, Limits};
+
+        struct Tree(usize);
+
+        impl IntoHtml for Tree {
+            fn into_html(self) -> impl IntoHtml {
+                div!((self.0 > 0).then(|| nested(Tree(self.0 - 1))))
+            }
+        }
+
+        let limits = Limits::new().max_len(100).max_depth(3);
+        assert_eq!(
+            limits.try_render(Tree(3)).unwrap(),
+            "<div><div><div><div></div></div></div></div>"
+        );
+        let error = limits.try_render(Tree(4)).unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&LimitExceeded::Depth(3)));
+
+        let error = limits
+            .try_render(ul!((0..1000).map(|i| li!(i)).collect::<Vec<_>>()))
+            .unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&LimitExceeded::Length(100)));
+
+        // An overestimated size hint is not an error.
+        assert_eq!(Limits::new().max_len(4).try_render(1.5f64).unwrap(), "1.5");
+
+        // Rendering stops at the first error instead of running through the
+        // remaining items.
+        let start = std::time::Instant::now();
+        let error = Limits::new()
+            .max_len(16)
+            .try_render(ul!((0..2_000_000_000u32).map(|i| li!(i))))
+            .unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&LimitExceeded::Length(16)));
+        let error = Limits::new()
+            .max_len(16)
+            .try_render(erased!(ul!((0..2_000_000_000u32).map(|i| li!(i)))))
+            .unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&LimitExceeded::Length(16)));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn render_to_slice() {
+        let status = |uptime: u32| {
+            (
+                DOCTYPE,
+                html!(body!(
+                    h1!(class = "status", "Device <1>"),
+                    a!(href = " /logs?since=1 day ", "Logs"),
+                    p!("Uptime: ", uptime, "s, load: ", 0.25)
+                )),
+            )
+        };
+
+        let mut bytes = [0; 256];
+        let html = status(42).render_to_slice(&mut bytes).unwrap();
+        assert_eq!(html, status(42).into_string());
+
+        let mut bytes = [0; 64];
+        let error = status(42).render_to_slice(&mut bytes).unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&limit::LimitExceeded::Length(64))
+        );
+
+        #[cfg(feature = "heapless")]
+        {
+            let mut page = heapless::String::<256>::new();
+            page.push_str("<!-- status -->").unwrap();
+            status(42).render_to_heapless(&mut page).unwrap();
+            assert_eq!(page.as_str(), format!("<!-- status -->{html}"));
+
+            let mut page = heapless::String::<64>::new();
+            page.push_str("<!-- status -->").unwrap();
+            let error = status(42).render_to_heapless(&mut page).unwrap_err();
+            assert_eq!(
+                error.downcast_ref(),
+                Some(&limit::LimitExceeded::Length(49))
+            );
+            assert_eq!(page, "<!-- status -->");
+        }
+    }
+
+    #[test]
+    fn static_templates() {
+        const HEAD: &str = static_html!(head!(
+            meta!(charset = "utf-8"),
+            title!("Q&A"),
+            script!(src = "/app.js", defer? = true)
+        ));
+        static NAV: &str = static_html!(minify!(nav!(
+            class = "main",
+            a!(href = "/", "Home"),
+            " \n ",
+            a!(href = "/about", "About")
+        )));
+
+        assert_eq!(
+            HEAD,
+            "<head><meta charset=\"utf-8\"><title>Q&amp;A</title><script \
+             src=\"/app.js\" defer></script></head>"
+        );
+        assert_eq!(
+            NAV,
+            "<nav class=main><a href=/>Home</a> <a href=/about>About</a></nav>"
+        );
+        assert_eq!(
+            body!(PreEscaped(NAV), main!("a < b")).into_string(),
+            "<body><nav class=main><a href=/>Home</a> <a \
+             href=/about>About</a></nav><main>a &lt; b</main></body>"
+        );
+    }
+
+    #[test]
+    fn const_fragments() {
+        const fn nav() -> PreEscaped<&'static str> {
+            PreEscaped(static_html!(nav!(a!(href = "/", "Home"))))
+        }
+
+        const PAGE: &str =
+            static_html!(body!(static_part!(nav()), main!("Hi")));
+        assert_eq!(
+            PAGE,
+            "<body><nav><a href=\"/\">Home</a></nav><main>Hi</main></body>"
+        );
+
+        let name = "a < b";
+        assert_eq!(
+            body!(
+                static_part!(nav()),
+                main!(name),
+                static_part!(PreEscaped("<hr>"))
+            )
+            .into_string(),
+            "<body><nav><a href=\"/\">Home</a></nav><main>a &lt; \
+             b</main><hr></body>"
+        );
+
+        const MAX_ITEMS: usize = 3;
+        assert_eq!(
+            div!(const { MAX_ITEMS }, const { "a<b" }).into_string(),
+            "<div>3a&lt;b</div>"
+        );
+    }
+
+    #[test]
+    fn layouts() {
+        struct Title(&'static str);
+
+        impl Title {
+            const fn new() -> Self {
+                Self("vy")
+            }
+        }
+
+        impl IntoHtml for Title {
+            fn into_html(self) -> impl IntoHtml {
+                self.0
+            }
+        }
+
+        layout! {
+            struct Base {
+                title = Title::new(),
+                head_extra = (),
+                content = (),
+                scripts = script!(src = "/app.js"),
+            } => (
+                DOCTYPE,
+                html!(head!(title!(title), head_extra), body!(content, scripts))
+            )
+        }
+
+        layout! {
+            /// A page of the docs, extending the base layout.
+            struct Docs {
+                title = "Docs",
+                nav = nav!(a!(href = "/docs", "Index")),
+                content = p!("TODO"),
+            } => Base::new()
+                .title(title)
+                .content((nav, article!(content)))
+        }
+
+        assert_eq!(
+            Base::default().into_string(),
+            "<!DOCTYPE html><html><head><title>vy</title></head><body><script \
+             src=\"/app.js\"></script></body></html>"
+        );
+
+        let name = String::from("a < b");
+        assert_eq!(
+            Base::new()
+                .content((h1!(&name), p!("Hi")))
+                .head_extra(meta!(name = "robots", content = "none"))
+                .scripts(())
+                .into_string(),
+            "<!DOCTYPE html><html><head><title>vy</title><meta \
+             name=\"robots\" content=\"none\"></head><body><h1>a &lt; \
+             b</h1><p>Hi</p></body></html>"
+        );
+        assert_eq!(
+            Docs::new().content(p!("Layouts")).into_string(),
+            "<!DOCTYPE html><html><head><title>Docs</title></\
+             head><body><nav><a \
+             href=\"/docs\">Index</a></nav><article><p>Layouts</p></\
+             article><script src=\"/app.js\"></script></body></html>"
+        );
+    }
+
+    #[test]
+    fn component_slots() {
+        #[component]
+        fn Card(
+            #[slot(default = "Untitled")] title: impl IntoHtml,
+            children: impl IntoHtml,
+            actions: impl IntoHtml,
+        ) -> impl IntoHtml {
+            article!(h2!(title), children, actions)
+        }
+
+        layout! {
+            struct Page {
+                content = (),
+            } => main!(content)
+        }
+
+        let name = "a < b";
+        assert_eq!(
+            div!(
+                Card(slot title = name, p!("Hi"), PreEscaped("<hr>")),
+                Card(slot actions = button!("OK")),
+                Some(PreEscaped("<br>"))
+            )
+            .into_string(),
+            "<div><article><h2>a &lt; \
+             b</h2><p>Hi</p><hr></article><article><h2>Untitled</\
+             h2><button>OK</button></article><br></div>"
+        );
+        assert_eq!(
+            body!(Page(slot content = Card(slot title = "Nested")))
+                .into_string(),
+            "<body><main><article><h2>Nested</h2></article></main></body>"
+        );
+        assert_eq!(
+            Card::new().children(p!("Hi")).into_string(),
+            "<article><h2>Untitled</h2><p>Hi</p></article>"
+        );
+        assert_eq!(
+            div!(Card(p!("Hi")), Card((p!("a"), p!("b")))).into_string(),
+            "<div><article><h2>Untitled</h2><p>Hi</p></\
+             article><article><h2>Untitled</h2><p>a</p><p>b</p></article></\
+             div>"
+        );
+    }
+
+    #[test]
+    fn partial_rendering() {
+        use crate::cache::{Cache, LruStore};
+
+        let cache = Cache::new(LruStore::new(8));
+        let page = |count: u32| {
+            body!(
+                h1!("Shop"),
+                cache.cached(count, move || {
+                    main!((1..count + 1).map(|i| {
+                        fragment!(format!("item-{i}"), p!("Item ", i))
+                    }))
+                }),
+                fragment!("cart", div!(id = "cart", count, " items"))
+            )
+        };
+
+        assert_eq!(
+            page(2).into_string(),
+            "<body><h1>Shop</h1><main><p>Item 1</p><p>Item 2</p></main><div \
+             id=\"cart\">2 items</div></body>"
+        );
+        assert_eq!(
+            fragment::render(page(2), "item-2").unwrap(),
+            "<p>Item 2</p>"
+        );
+        assert_eq!(
+            fragment::render(page(3), "cart").unwrap(),
+            "<div id=\"cart\">3 items</div>"
+        );
+        assert_eq!(page(3).into_string().matches("Item").count(), 3);
+        assert!(fragment::render(page(2), "item-3").is_err());
+    }
+
+    #[test]
+    fn minify_static_content() {
+        let name = "  Ann  ";
+        assert_eq!(
+            minify!(main!(
+                id = "app",
+                title = "two words",
+                data_n = 3,
+                h1!("  Hello,\n\t", name, "  "),
+                p!("a  ", "  b", b!(" c ")),
+                pre!("  keep\n  this  "),
+                textarea!(" and\n this ")
+            ))
+            .into_string(),
+            "<main id=app title=\"two words\" data_n=3><h1> Hello,   Ann   \
+             </h1><p>a b<b> c </b></p><pre>  keep\n  this  </pre><textarea> \
+             and\n this </textarea></main>"
+        );
+
+        let script = || {
+            minify!(script!(
+                "// Greet the user\n",
+                "const greeting = 'a  b'; /* inline */\n",
+                "if (greeting.length != 0) {\n",
+                "    console.log(greeting, 1 / 2, /[/]\\//.test(x));\n",
+                "}\n",
+                "let n = a\n",
+                "++b\n",
+                "function f(s) { return /a  b/.test(s) || typeof /c  d/ }\n",
+                "let m = x.return / 2 / y"
+            ))
+        };
+        assert_eq!(
+            script().into_string(),
+            "<script>const greeting='a  \
+             b';if(greeting.length!=0){console.log(greeting,1/2,/[/]\\//.\
+             test(x));}\nlet n=a\n++b\nfunction f(s){return/a  \
+             b/.test(s)||typeof/c  d/}\nlet m=x.return/2/y</script>"
+        );
+        assert_eq!(
+            csp_hash!(minify!(style!("a {\n  color: red;\n}\n/* x */"))),
+            csp_hash!("a{color:red}")
+        );
+        assert_eq!(
+            minify!(style!(
+                "a:hover, b  ~ c {\n  font: 12px 'A  B';\n  margin: 0 auto;\n}"
+            ))
+            .into_string(),
+            "<style>a:hover,b ~ c{font:12px 'A  B';margin:0 auto}</style>"
+        );
+    }
+
+    #[test]
+    fn url_attributes() {
+        let url = "javascript:alert(1)";
+        assert_eq!(
+            a!(href = url, "x").into_string(),
+            "<a href=\"about:invalid#vy-unsafe-url\">x</a>"
+        );
+        assert_eq!(
+            a!(href = "javascript:void(0)").into_string(),
+            "<a href=\"javascript:void(0)\"></a>"
+        );
+        assert_eq!(
+            a!(href = if true { url } else { "/" }).into_string(),
+            "<a href=\"about:invalid#vy-unsafe-url\"></a>"
+        );
+        assert_eq!(
+            form!(action? = Some(url), img!(src = "/a b.png")).into_string(),
+            "<form action=\"about:invalid#vy-unsafe-url\"><img src=\"/a \
+             b.png\"></form>"
+        );
+        assert_eq!(
+            img!(src = format!("/{}.png", "a b")).into_string(),
+            "<img src=\"/a%20b.png\">"
+        );
+        assert_eq!(
+            img!(srcset = format!("a.png 1x, {url} 2x")).into_string(),
+            "<img srcset=\"\">"
+        );
+    }
+
+    #[test]
+    fn url_builder() {
+        let id = "a b";
+        let query: Option<&str> = None;
+        let href = url!("/users/{id}/{{raw}}", id = id; q? = query, "x[]" = 1);
+        assert_eq!(href.size_hint(), "/users/a%20b/{raw}?x%5B%5D=1".len());
+        assert_eq!(
+            a!(href = href).into_string(),
+            "<a href=\"/users/a%20b/{raw}?x%5B%5D=1\"></a>"
+        );
+        assert_eq!(
+            a!(href = url!("/search?lang=en"; q = "<&>", page = 2))
+                .into_string(),
+            "<a href=\"/search?lang=en&amp;q=%3C%26%3E&amp;page=2\"></a>"
+        );
+        assert_eq!(
+            a!(href = url!("{id}")).into_string(),
+            "<a href=\"a%20b\"></a>"
+        );
+    }
+
+    #[test]
+    fn typed_routes() {
+        #[derive(Debug, PartialEq, Route)]
+        enum Page {
+            #[route("/")]
+            Home,
+            #[route("/users/{id}/posts/{slug}")]
+            Post { slug: String, id: u32 },
+            #[route("/files/{0}")]
+            File(String),
+        }
+
+        let post = Page::Post {
+            id: 1,
+            slug: "a b".into(),
+        };
+        assert_eq!(
+            form!(action = &post, a!(href = Page::Home)).into_string(),
+            "<form action=\"/users/1/posts/a%20b\"><a href=\"/\"></a></form>"
+        );
+        assert_eq!(Page::File("<&>".into()).to_path(), "/files/%3C%26%3E");
+        assert_eq!(
+            a!(href = Page::File("<&>".into())).into_string(),
+            "<a href=\"/files/%3C%26%3E\"></a>"
+        );
+
+        assert_eq!(Page::from_path(&post.to_string()), Some(post));
+        assert_eq!(
+            Page::from_path("/files/%3C&%3E#top"),
+            Some(Page::File("<&>".into()))
+        );
+        assert_eq!(Page::from_path(""), None);
+        assert_eq!(Page::from_path("/users/x/posts/y"), None);
+        assert_eq!(Page::from_path("/files/a/b"), None);
+    }
+
+    #[test]
+    fn escape_policy() {
+        use escape::{with_policy, EscapePolicy};
+
+        let name = "O'Brien";
+        let html = || {
+            (
+                span!(title = name, name),
+                with_policy(EscapePolicy::Xml, span!(name, "\u{1}")),
+            )
+        };
+        assert_eq!(
+            html().into_string(),
+            "<span title=\"O'Brien\">O'Brien</span><span>O&#39;Brien\u{1}</\
+             span>"
+        );
+
+        let mut buf = Buffer::new();
+        buf.set_escape_policy(EscapePolicy::Strict);
+        html().render_into(&mut buf);
+        assert_eq!(
+            buf.into_string(),
+            "<span title=\"O&#x27;Brien\">O&#x27;Brien</span><span>O&#39;\
+             Brien\u{1}</span>"
+        );
+    }
+
+    #[test]
+    fn text_content() {
+        let body = || {
+            main!(
+                h1!("Q&A"),
+                p!("Prices ", b!(r#"<"cheap">"#), " &amp; more"),
+                script!("let x = 1 < 2;")
+            )
+        };
+        let description = body().into_text();
+        assert_eq!(description, r#"Q&A Prices <"cheap"> &amp; more"#);
+        assert_eq!(
+            meta!(name = "description", content = description).into_string(),
+            "<meta name=\"description\" content=\"Q&amp;A Prices \
+             &lt;&quot;cheap&quot;&gt; &amp;amp; more\">"
+        );
+        assert_eq!(escape::unescape("&lt;b&gt;"), "<b>");
+    }
+
+    #[test]
+    fn pretty_output() {
+        let page = || {
+            div!(
+                class = "card",
+                h2!("Title ", em!("here")),
+                p!("Some ", a!(href = "/", "link"), "."),
+                textarea!("  keep\n  this")
+            )
+        };
+        assert_eq!(
+            page().into_pretty_string(),
+            "<div class=\"card\">\n  <h2>Title <em>here</em></h2>\n  <p>Some \
+             <a href=\"/\">link</a>.</p>\n  <textarea>  keep\n  \
+             this</textarea>\n</div>"
+        );
+        assert_eq!(
+            erased!(div!(
+                class = "card",
+                h2!("Title ", em!("here")),
+                p!("Some ", a!(href = "/", "link"), "."),
+                textarea!("  keep\n  this")
+            ))
+            .into_pretty_string(),
+            page().into_pretty_string()
+        );
+        assert_eq!(
+            page().into_string(),
+            "<div class=\"card\"><h2>Title <em>here</em></h2><p>Some <a \
+             href=\"/\">link</a>.</p><textarea>  keep\n  this</textarea></div>"
+        );
+    }
+
     #[test]
     fn attributes_maybe_bool() {
         assert_eq!(